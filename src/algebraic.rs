@@ -0,0 +1,106 @@
+use crate::hittable::{HitRecord, Hittable};
+use crate::polynomial::Polynomial3;
+use crate::ray::Ray;
+use crate::vec3::{normalise, Point3, Vec3};
+
+// An algebraic surface, the zero set of a polynomial F(x, y, z) such as a torus, Steiner's Roman
+// surface or Kummer's quartic. Along a ray F is a polynomial in t, so rather than march we find
+// its first root exactly. F should be negative inside, if the surface has one.
+pub struct Algebraic {
+    f: Polynomial3,
+    gradient: [Polynomial3; 3],
+}
+
+impl Algebraic {
+    pub fn new(f: Polynomial3) -> Algebraic {
+        let gradient = [f.derivative(0), f.derivative(1), f.derivative(2)];
+        Algebraic { f, gradient }
+    }
+
+    // the quartic (|q|^2 + R^2 - r^2)^2 - 4 R^2 (|q|^2 - (q.a)^2) with q the offset from center
+    pub fn torus(center: Point3, axis: Vec3, major_radius: f64, minor_radius: f64) -> Algebraic {
+        let a = normalise(axis);
+        let q = [
+            Polynomial3::variable(0) + -center.x(),
+            Polynomial3::variable(1) + -center.y(),
+            Polynomial3::variable(2) + -center.z(),
+        ];
+        let q2 = q
+            .iter()
+            .fold(Polynomial3::default(), |acc, c| acc + c.powi(2));
+        let qa = q[0].clone() * a.x() + q[1].clone() * a.y() + q[2].clone() * a.z();
+
+        let (r2, s2) = (major_radius * major_radius, minor_radius * minor_radius);
+        Algebraic::new((q2.clone() + (r2 - s2)).powi(2) - (q2 - qa.powi(2)) * (4.0 * r2))
+    }
+
+    pub fn value(&self, v: Point3) -> f64 {
+        self.f.eval(v)
+    }
+
+    pub fn gradient(&self, v: Point3) -> Vec3 {
+        Vec3::new(
+            self.gradient[0].eval(v),
+            self.gradient[1].eval(v),
+            self.gradient[2].eval(v),
+        )
+    }
+}
+
+impl Hittable for Algebraic {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let p = self.f.along(r.origin(), r.direction());
+        let root = p.first_root(t_min, t_max)?;
+        if root >= t_max {
+            return None;
+        }
+
+        let mut rec = HitRecord::new();
+        rec.t = root;
+        rec.p = r.at(rec.t);
+
+        // convention we have chosen
+        let outward_normal = normalise(self.gradient(rec.p));
+        rec.set_face_normal(r, outward_normal);
+
+        Some(rec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::vec3::{eq, origin, unit_x, unit_y, unit_z};
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_algebraic_torus() {
+        let t = Algebraic::torus(origin(), unit_y(), 2.0, 0.5);
+        assert_eq!(t.f.degree(), 4);
+        assert!(t.value(2.0 * unit_x()) < 0.0);
+        assert!(t.value(origin()) > 0.0);
+        assert_relative_eq!(t.value(2.5 * unit_z()), 0.0, epsilon = 1e-9);
+
+        // through the hole the ray crosses the tube twice on each side, first at x = -2.5
+        let r = Ray::new(-5.0 * unit_x(), unit_x());
+        let rec = t.hit(&r, 0.0, f64::INFINITY).unwrap();
+        assert_relative_eq!(rec.t, 2.5, epsilon = 1e-9);
+        assert!(eq(rec.normal, -unit_x()));
+        assert!(rec.front_face);
+
+        // and from inside the tube, the far side of it
+        let r = Ray::new(-2.0 * unit_x(), unit_x());
+        let rec = t.hit(&r, 0.0, f64::INFINITY).unwrap();
+        assert_relative_eq!(rec.t, 0.5, epsilon = 1e-9);
+        assert!(!rec.front_face);
+
+        // missing the tube altogether
+        let r = Ray::new(Vec3::new(-5.0, 1.0, 0.0), unit_x());
+        assert!(t.hit(&r, 0.0, f64::INFINITY).is_none());
+        let r = Ray::new(-5.0 * unit_x(), unit_x());
+        assert!(t.hit(&r, 0.0, 2.0).is_none());
+    }
+}
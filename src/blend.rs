@@ -0,0 +1,277 @@
+use serde_derive::Deserialize;
+
+use crate::dual::{Dual, Scalar};
+use crate::hittable::ImplicitSurface;
+use crate::interval::Interval;
+use crate::vec3::Vec3;
+
+// Smooth versions of the CSG operations, which round off the crease where two surfaces meet with
+// a fillet of roughly radius k. See https://iquilezles.org/articles/smin/ for the derivations.
+//
+// The blends are written against Scalar, so running them on the children's dual distances gives
+// the exact gradient of the blended distance. That gradient is a weighted average of the
+// children's, so the larger of their Lipschitz bounds holds for the blend too. Running them on
+// intervals likewise bounds the blend over a box.
+
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Blend {
+    // quadratic polynomial, identical to min outside the blend region
+    #[default]
+    Polynomial,
+    // log-sum-exp, smooth everywhere but pulls the surface in by up to k ln 2 away from the blend
+    Exponential,
+}
+
+// smooth minimum of a and b
+fn smin<T: Scalar>(a: T, b: T, k: f64, blend: Blend) -> T {
+    match blend {
+        Blend::Polynomial => {
+            let h = ((b - a) / k * 0.5 + 0.5).clamp(0.0, 1.0);
+            b + h * (a - b) - h * (-h + 1.0) * k
+        }
+        Blend::Exponential => {
+            // shift by the minimum before exponentiating so nothing overflows
+            let m = a.min(b);
+            let ea = (-(a - m) / k).exp();
+            let eb = (-(b - m) / k).exp();
+            m - (ea + eb).ln() * k
+        }
+    }
+}
+
+// smooth maximum is the smooth minimum of the complements
+fn smax<T: Scalar>(a: T, b: T, k: f64, blend: Blend) -> T {
+    -smin(-a, -b, k, blend)
+}
+
+pub struct SmoothUnion {
+    a: Box<dyn ImplicitSurface>,
+    b: Box<dyn ImplicitSurface>,
+    k: f64,
+    blend: Blend,
+}
+
+impl SmoothUnion {
+    pub fn new(
+        a: Box<dyn ImplicitSurface>,
+        b: Box<dyn ImplicitSurface>,
+        k: f64,
+        blend: Blend,
+    ) -> SmoothUnion {
+        SmoothUnion { a, b, k, blend }
+    }
+}
+
+impl ImplicitSurface for SmoothUnion {
+    fn signed_distance(&self, v: Vec3) -> f64 {
+        let (a, b) = (self.a.signed_distance(v), self.b.signed_distance(v));
+        smin(a, b, self.k, self.blend)
+    }
+
+    fn signed_distance_dual(&self, v: [Dual; 3]) -> Dual {
+        let (a, b) = (
+            self.a.signed_distance_dual(v),
+            self.b.signed_distance_dual(v),
+        );
+        smin(a, b, self.k, self.blend)
+    }
+
+    fn lipschitz(&self, v: Vec3, radius: f64) -> f64 {
+        f64::max(self.a.lipschitz(v, radius), self.b.lipschitz(v, radius))
+    }
+
+    fn signed_distance_interval(&self, v: [Interval; 3]) -> Interval {
+        let (a, b) = (
+            self.a.signed_distance_interval(v),
+            self.b.signed_distance_interval(v),
+        );
+        smin(a, b, self.k, self.blend)
+    }
+}
+
+pub struct SmoothIntersection {
+    a: Box<dyn ImplicitSurface>,
+    b: Box<dyn ImplicitSurface>,
+    k: f64,
+    blend: Blend,
+}
+
+impl SmoothIntersection {
+    pub fn new(
+        a: Box<dyn ImplicitSurface>,
+        b: Box<dyn ImplicitSurface>,
+        k: f64,
+        blend: Blend,
+    ) -> SmoothIntersection {
+        SmoothIntersection { a, b, k, blend }
+    }
+}
+
+impl ImplicitSurface for SmoothIntersection {
+    fn signed_distance(&self, v: Vec3) -> f64 {
+        let (a, b) = (self.a.signed_distance(v), self.b.signed_distance(v));
+        smax(a, b, self.k, self.blend)
+    }
+
+    fn signed_distance_dual(&self, v: [Dual; 3]) -> Dual {
+        let (a, b) = (
+            self.a.signed_distance_dual(v),
+            self.b.signed_distance_dual(v),
+        );
+        smax(a, b, self.k, self.blend)
+    }
+
+    fn lipschitz(&self, v: Vec3, radius: f64) -> f64 {
+        f64::max(self.a.lipschitz(v, radius), self.b.lipschitz(v, radius))
+    }
+
+    fn signed_distance_interval(&self, v: [Interval; 3]) -> Interval {
+        let (a, b) = (
+            self.a.signed_distance_interval(v),
+            self.b.signed_distance_interval(v),
+        );
+        smax(a, b, self.k, self.blend)
+    }
+}
+
+// b cut out of a, with the edge of the cut rounded off
+pub struct SmoothDifference {
+    a: Box<dyn ImplicitSurface>,
+    b: Box<dyn ImplicitSurface>,
+    k: f64,
+    blend: Blend,
+}
+
+impl SmoothDifference {
+    pub fn new(
+        a: Box<dyn ImplicitSurface>,
+        b: Box<dyn ImplicitSurface>,
+        k: f64,
+        blend: Blend,
+    ) -> SmoothDifference {
+        SmoothDifference { a, b, k, blend }
+    }
+}
+
+impl ImplicitSurface for SmoothDifference {
+    fn signed_distance(&self, v: Vec3) -> f64 {
+        let (a, b) = (self.a.signed_distance(v), self.b.signed_distance(v));
+        smax(a, -b, self.k, self.blend)
+    }
+
+    fn signed_distance_dual(&self, v: [Dual; 3]) -> Dual {
+        let (a, b) = (
+            self.a.signed_distance_dual(v),
+            self.b.signed_distance_dual(v),
+        );
+        smax(a, -b, self.k, self.blend)
+    }
+
+    fn lipschitz(&self, v: Vec3, radius: f64) -> f64 {
+        f64::max(self.a.lipschitz(v, radius), self.b.lipschitz(v, radius))
+    }
+
+    fn signed_distance_interval(&self, v: [Interval; 3]) -> Interval {
+        let (a, b) = (
+            self.a.signed_distance_interval(v),
+            self.b.signed_distance_interval(v),
+        );
+        smax(a, -b, self.k, self.blend)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::sphere::Sphere;
+    use crate::vec3::{eq, origin, unit_x, unit_y, unit_z};
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    // unit spheres at x = -1 and x = 1, just touching at the origin
+    fn pair() -> (Box<dyn ImplicitSurface>, Box<dyn ImplicitSurface>) {
+        (
+            Box::new(Sphere::new(-unit_x(), 1.0)),
+            Box::new(Sphere::new(unit_x(), 1.0)),
+        )
+    }
+
+    // central difference of the field along each axis
+    fn numeric_gradient(s: &dyn ImplicitSurface, v: Vec3) -> Vec3 {
+        let h = 1e-6;
+        let dx = s.signed_distance(v + h * unit_x()) - s.signed_distance(v - h * unit_x());
+        let dy = s.signed_distance(v + h * unit_y()) - s.signed_distance(v - h * unit_y());
+        let dz = s.signed_distance(v + h * unit_z()) - s.signed_distance(v - h * unit_z());
+        Vec3::new(dx, dy, dz) / (2.0 * h)
+    }
+
+    #[test]
+    fn test_smin_matches_min_away_from_blend() {
+        // a varies along x and b along y, so the gradient says how much each contributes
+        let a = |x| Dual::new(x, unit_x());
+        let b = |y| Dual::new(y, unit_y());
+
+        let d = smin(a(0.0), b(5.0), 0.5, Blend::Polynomial);
+        assert_relative_eq!(d.value, 0.0);
+        assert!(eq(d.grad, unit_x()));
+
+        let d = smin(a(5.0), b(0.0), 0.5, Blend::Polynomial);
+        assert_relative_eq!(d.value, 0.0);
+        assert!(eq(d.grad, unit_y()));
+
+        let d = smin(0.0, 50.0, 0.5, Blend::Exponential);
+        assert_relative_eq!(d, 0.0, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn test_smooth_union_fills_crease() {
+        let (a, b) = pair();
+        let u = SmoothUnion::new(a, b, 0.5, Blend::Polynomial);
+
+        // hard union would be 0 at the origin, the blend pushes it inside
+        assert_relative_eq!(u.signed_distance(origin()), -0.125);
+
+        // but far from the join it's unchanged
+        assert_relative_eq!(u.signed_distance(2.0 * unit_x()), 0.0);
+        assert_relative_eq!(u.signed_distance(-3.0 * unit_x()), 1.0);
+    }
+
+    #[test]
+    fn test_smooth_intersection_and_difference() {
+        let (a, b) = pair();
+        let i = SmoothIntersection::new(a, b, 0.5, Blend::Polynomial);
+        assert_relative_eq!(i.signed_distance(origin()), 0.125);
+
+        let (a, b) = pair();
+        let d = SmoothDifference::new(a, b, 0.5, Blend::Polynomial);
+        assert_relative_eq!(d.signed_distance(-2.0 * unit_x()), 0.0);
+        assert_relative_eq!(d.signed_distance(origin()), 0.125);
+    }
+
+    #[test]
+    fn test_blended_gradients() {
+        let points = [
+            Vec3::new(0.1, 0.9, 0.0),
+            Vec3::new(-0.2, 1.1, 0.3),
+            Vec3::new(0.05, 0.5, -0.6),
+        ];
+
+        for blend in [Blend::Polynomial, Blend::Exponential] {
+            let (a, b) = pair();
+            let u = SmoothUnion::new(a, b, 0.5, blend);
+            let (a, b) = pair();
+            let i = SmoothIntersection::new(a, b, 0.5, blend);
+            let (a, b) = pair();
+            let d = SmoothDifference::new(a, b, 0.5, blend);
+
+            for s in [&u as &dyn ImplicitSurface, &i, &d] {
+                for v in points {
+                    assert!(eq(s.gradient(v), numeric_gradient(s, v)));
+                }
+            }
+        }
+    }
+}
@@ -1,38 +1,287 @@
-use crate::ray::Ray;
-use crate::vec3::{Point3, Vec3};
+use config::ConfigError;
+use serde_derive::Deserialize;
 
+use crate::diagnostics::Diagnostics;
+use crate::hittable_list::HittableList;
+use crate::ray::{MarchSettings, Ray};
+use crate::vec3::{self, cross, normalise, unit_y, Point3, Vec3};
+
+// The [camera] section of the config. The aspect ratio isn't here as it's the image's, from
+// [view].
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct CameraSettings {
+    pub look_from: [f64; 3],
+    pub look_at: [f64; 3],
+    // which way is up, which only needs to be roughly so
+    pub vup: [f64; 3],
+    // the vertical field of view in degrees
+    pub vfov: f64,
+    // the diameter of the lens, 0 for a pinhole with everything in focus
+    pub aperture: f64,
+    // how far in front of the camera things are in focus, by default at look_at
+    pub focus_distance: Option<f64>,
+    // focus on whatever is at the centre of the image instead, if anything is
+    pub autofocus: bool,
+}
+
+// looking down -z from the origin, as the camera always used to
+impl Default for CameraSettings {
+    fn default() -> CameraSettings {
+        CameraSettings {
+            look_from: [0.0, 0.0, 0.0],
+            look_at: [0.0, 0.0, -1.0],
+            vup: [0.0, 1.0, 0.0],
+            vfov: 90.0,
+            aperture: 0.0,
+            focus_distance: None,
+            autofocus: false,
+        }
+    }
+}
+
+impl CameraSettings {
+    // the camera these describe, for an image with the given aspect ratio (width / height) of
+    // world, which is only looked at to autofocus
+    pub fn camera(
+        &self,
+        aspect_ratio: f64,
+        world: &HittableList,
+        march: &MarchSettings,
+    ) -> Result<Camera, ConfigError> {
+        let vec = |v: [f64; 3]| Vec3::new(v[0], v[1], v[2]);
+        let invalid = |e: &str| ConfigError::Message(format!("camera: {}", e));
+
+        let (look_from, look_at, vup) = (vec(self.look_from), vec(self.look_at), vec(self.vup));
+        if self.look_from == self.look_at {
+            return Err(invalid("look_from and look_at must be different points"));
+        }
+        if cross(look_at - look_from, vup).length_squared() == 0.0 {
+            return Err(invalid("vup must not be along the direction looked in"));
+        }
+        if !(self.vfov > 0.0 && self.vfov < 180.0) {
+            return Err(invalid("vfov must be between 0 and 180 degrees"));
+        }
+        if self.aperture < 0.0 {
+            return Err(invalid("aperture must not be negative"));
+        }
+        let focus_distance = match self.focus_distance {
+            None => (look_at - look_from).length(),
+            Some(d) if d > 0.0 => d,
+            Some(_) => return Err(invalid("focus_distance must be positive")),
+        };
+
+        let camera = |focus_distance: f64| {
+            Camera::new(
+                look_from,
+                look_at,
+                vup,
+                self.vfov.to_radians(),
+                aspect_ratio,
+                self.aperture,
+                focus_distance,
+            )
+        };
+        if !self.autofocus {
+            return Ok(camera(focus_distance));
+        }
+
+        // The ray through the centre of the image from the centre of the lens is along the
+        // direction looked in, so the distance to a hit on it is how far in front of the camera
+        // that is. Traces for this aren't part of the render, so aren't kept in its diagnostics.
+        let centre = Ray::new(look_from, look_at - look_from);
+        let hit = world.hit(
+            &centre,
+            0.0,
+            f64::INFINITY,
+            march,
+            &mut Diagnostics::default(),
+        );
+        Ok(camera(hit.map_or(focus_distance, |rec| rec.t)))
+    }
+}
+
+// A thin lens camera. Rays for a point on the image start from a random point on the lens and
+// all pass through the same point on the plane in focus, so only things near that plane are
+// sharp.
 pub struct Camera {
     origin: Point3,
     lower_left_corner: Point3,
     horizontal: Vec3,
     vertical: Vec3,
+    // right and up in the image, to place points on the lens
+    u: Vec3,
+    v: Vec3,
+    lens_radius: f64,
 }
 
 impl Camera {
-    pub fn new() -> Camera {
-        let aspect_ratio = 16.0 / 9.0;
-        let viewport_height = 2.0;
+    // A camera at look_from looking at look_at, with vup upright in the image, seeing vfov
+    // radians from bottom to top of an image aspect_ratio times as wide as it is high. Its lens
+    // is aperture across and focused focus_distance in front of it.
+    pub fn new(
+        look_from: Point3,
+        look_at: Point3,
+        vup: Vec3,
+        vfov: f64,
+        aspect_ratio: f64,
+        aperture: f64,
+        focus_distance: f64,
+    ) -> Camera {
+        let viewport_height = 2.0 * (vfov / 2.0).tan();
         let viewport_width = aspect_ratio * viewport_height;
-        let focal_length = 1.0;
 
-        let origin = Point3::new(0.0, 0.0, 0.0);
-        let horizontal = Vec3::new(viewport_width, 0.0, 0.0);
-        let vertical = Vec3::new(0.0, viewport_height, 0.0);
-        let lower_left_corner =
-            origin - horizontal / 2.0 - vertical / 2.0 - Vec3::new(0.0, 0.0, focal_length);
+        // w points back from what we look at, u to the right and v up
+        let w = normalise(look_from - look_at);
+        let u = normalise(cross(vup, w));
+        let v = cross(w, u);
+
+        // the viewport is on the plane in focus
+        let origin = look_from;
+        let horizontal = focus_distance * viewport_width * u;
+        let vertical = focus_distance * viewport_height * v;
+        let lower_left_corner = origin - horizontal / 2.0 - vertical / 2.0 - focus_distance * w;
 
         Camera {
             origin,
             lower_left_corner,
             horizontal,
             vertical,
+            u,
+            v,
+            lens_radius: aperture / 2.0,
         }
     }
 
-    pub fn get_ray(&self, u: f64, v: f64) -> Ray {
+    pub fn get_ray(&self, s: f64, t: f64) -> Ray {
+        let rd = self.lens_radius * vec3::random_in_unit_disk();
+        let offset = rd.x() * self.u + rd.y() * self.v;
         Ray::new(
-            self.origin,
-            self.lower_left_corner + u * self.horizontal + v * self.vertical - self.origin,
+            self.origin + offset,
+            self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
+        )
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 0.0, -1.0),
+            unit_y(),
+            90.0f64.to_radians(),
+            16.0 / 9.0,
+            0.0,
+            1.0,
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::sphere::Sphere;
+    use crate::vec3::{dot, eq, unit_x, unit_z};
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_camera_default() {
+        // the viewport 2 high and 16 / 9 times that wide, 1 in front of the origin
+        let cam = Camera::default();
+        assert!(eq(cam.get_ray(0.5, 0.5).direction(), -unit_z()));
+        assert!(eq(
+            cam.get_ray(0.0, 0.0).direction(),
+            normalise(Vec3::new(-16.0 / 9.0, -1.0, -1.0))
+        ));
+    }
+
+    #[test]
+    fn test_camera_look_at() {
+        let settings = CameraSettings {
+            look_from: [5.0, 0.0, 0.0],
+            look_at: [0.0, 0.0, 0.0],
+            vup: [0.0, 1.0, 0.0],
+            vfov: 60.0,
+            ..Default::default()
+        };
+        let cam = settings
+            .camera(2.0, &HittableList::new(), &MarchSettings::default())
+            .unwrap();
+
+        let centre = cam.get_ray(0.5, 0.5);
+        assert!(eq(centre.origin(), 5.0 * unit_x()));
+        assert!(eq(centre.direction(), -unit_x()));
+
+        // the top of the image is vfov / 2 above the centre, and upright
+        let top = cam.get_ray(0.5, 1.0).direction();
+        assert_relative_eq!(dot(top, centre.direction()), 30.0f64.to_radians().cos());
+        assert!(top.y() > 0.0);
+
+        // and looking down -x, right is -z
+        assert!(cam.get_ray(1.0, 0.5).direction().z() < 0.0);
+    }
+
+    #[test]
+    fn test_camera_settings_errors() {
+        let error = |s: CameraSettings| {
+            s.camera(1.0, &HittableList::new(), &MarchSettings::default())
+                .err()
+                .unwrap()
+                .to_string()
+        };
+        let looking_up = CameraSettings {
+            look_at: [0.0, 1.0, 0.0],
+            ..Default::default()
+        };
+        assert_eq!(
+            error(looking_up),
+            "camera: vup must not be along the direction looked in"
+        );
+        let wide = CameraSettings {
+            vfov: 180.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            error(wide),
+            "camera: vfov must be between 0 and 180 degrees"
+        );
+    }
+
+    #[test]
+    fn test_camera_depth_of_field() {
+        // a sphere 4 in front, its nearest point 3 away
+        let mut world = HittableList::new();
+        world.add_implicit(Box::new(Sphere::new(-4.0 * unit_z(), 1.0)));
+        let march = MarchSettings::default();
+
+        let settings = CameraSettings {
+            aperture: 0.5,
+            ..Default::default()
+        };
+        let autofocus = CameraSettings {
+            autofocus: true,
+            ..settings.clone()
+        };
+
+        // in focus at look_at, or at the sphere when autofocused
+        for (s, focus) in [(settings, 1.0), (autofocus, 3.0)] {
+            let cam = s.camera(1.0, &world, &march).unwrap();
+
+            // rays for a point on the image start all over the lens but meet on the focus plane
+            let mut spread: f64 = 0.0;
+            let meet = |r: &Ray| r.at(focus / -r.direction().z());
+            let first = meet(&cam.get_ray(0.7, 0.4));
+            for _ in 0..100 {
+                let r = cam.get_ray(0.7, 0.4);
+                assert!(r.origin().length() <= 0.25);
+                spread = spread.max(r.origin().length());
+                assert!(eq(meet(&r), first));
+            }
+            assert!(spread > 0.1);
+            assert_relative_eq!(first.z(), -focus, epsilon = 1e-7);
+        }
+    }
+}
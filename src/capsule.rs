@@ -0,0 +1,69 @@
+use crate::dual::{self, Dual, Scalar};
+use crate::hittable::ImplicitSurface;
+use crate::vec3::{dot, Point3, Vec3};
+
+// A cylinder with hemispherical ends, i.e. all points within radius of the segment a-b
+#[derive(Copy, Clone, Default)]
+pub struct Capsule {
+    a: Point3,
+    b: Point3,
+    radius: f64,
+}
+
+impl Capsule {
+    pub fn new(a: Point3, b: Point3, r: f64) -> Capsule {
+        Capsule { a, b, radius: r }
+    }
+
+    // signed distance function for a capsule is the distance to its spine segment minus its
+    // radius, where h is how far along the segment the closest point to v is
+    fn distance<T: Scalar>(&self, v: [T; 3]) -> T {
+        let ab = self.b - self.a;
+        let q = dual::offset(v, self.a);
+        let h = (dual::dot(q, ab) / dot(ab, ab)).clamp(0.0, 1.0);
+        dual::length(dual::sub(q, dual::along(h, ab))) - self.radius
+    }
+}
+
+impl ImplicitSurface for Capsule {
+    fn signed_distance(&self, v: Vec3) -> f64 {
+        self.distance(dual::constant(v))
+    }
+
+    fn signed_distance_dual(&self, v: [Dual; 3]) -> Dual {
+        self.distance(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vec3::{eq, origin, unit_x, unit_y, unit_z};
+    use approx::assert_relative_eq;
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_capsule_sdf_coi() {
+        let c = Capsule::new(origin(), 2.0 * unit_z(), 1.0);
+        assert_relative_eq!(c.signed_distance(unit_x()), 0.0);
+        assert_relative_eq!(c.signed_distance(unit_y() + unit_z()), 0.0);
+        assert_relative_eq!(c.signed_distance(3.0 * unit_z()), 0.0);
+        assert_relative_eq!(c.signed_distance(-unit_z()), 0.0);
+    }
+
+    #[test]
+    fn test_capsule_sdf_outside_inside() {
+        let c = Capsule::new(origin(), 2.0 * unit_z(), 1.0);
+        assert_relative_eq!(c.signed_distance(unit_z()), -1.0);
+        assert_relative_eq!(c.signed_distance(5.0 * unit_x() + unit_z()), 4.0);
+        assert_relative_eq!(c.signed_distance(5.0 * unit_z()), 2.0);
+    }
+
+    #[test]
+    fn test_capsule_gradient() {
+        let c = Capsule::new(origin(), 2.0 * unit_z(), 1.0);
+        assert!(eq(c.gradient(2.0 * unit_x() + unit_z()), unit_x()));
+        assert!(eq(c.gradient(-2.0 * unit_z()), -unit_z()));
+    }
+}
@@ -0,0 +1,109 @@
+use crate::dual::{self, Dual};
+use crate::hittable::ImplicitSurface;
+use crate::profile::{self, Point2};
+use crate::vec3::{dot, normalise, perpendicular, Point3, Vec3};
+
+// A cone truncated by flat caps at a and b, with radius ra at a and rb at b. Either radius may be
+// zero to give a pointed cone.
+#[derive(Copy, Clone, Default)]
+pub struct CappedCone {
+    a: Point3,
+    axis: Vec3,
+    // the half-plane profile, running counter-clockwise from the centre of the cap at a around to
+    // the centre of the cap at b
+    profile: [Point2; 4],
+}
+
+impl CappedCone {
+    pub fn new(a: Point3, b: Point3, ra: f64, rb: f64) -> CappedCone {
+        let h = (b - a).length();
+        CappedCone {
+            a,
+            axis: normalise(b - a),
+            profile: [[0.0, 0.0], [ra, 0.0], [rb, h], [0.0, h]],
+        }
+    }
+
+    // project v into the profile half-plane: (distance from the axis, height along the axis),
+    // along with the direction radially out from the axis
+    fn profile_coords(&self, v: Vec3) -> (Point2, Vec3) {
+        let q = v - self.a;
+        let y = dot(q, self.axis);
+        let radial = q - y * self.axis;
+        let x = radial.length();
+
+        let dir = if x > 0.0 {
+            radial / x
+        } else {
+            perpendicular(self.axis)
+        };
+
+        ([x, y], dir)
+    }
+}
+
+impl ImplicitSurface for CappedCone {
+    // The cone is a surface of revolution, so its distance is that of the 2D profile. The edge of
+    // the profile along the axis is left out of the boundary since it is not part of the surface.
+    fn signed_distance(&self, v: Vec3) -> f64 {
+        let (q, _) = self.profile_coords(v);
+        profile::signed_distance(q, &self.profile, &self.profile).0
+    }
+
+    fn gradient(&self, v: Vec3) -> Vec3 {
+        let (q, radial) = self.profile_coords(v);
+        let (_, g) = profile::signed_distance(q, &self.profile, &self.profile);
+        g[0] * radial + g[1] * self.axis
+    }
+
+    fn signed_distance_dual(&self, v: [Dual; 3]) -> Dual {
+        let p = dual::value(v);
+        dual::chain(v, self.signed_distance(p), self.gradient(p))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vec3::{eq, origin, unit_x, unit_y, unit_z};
+    use approx::assert_relative_eq;
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_cone_sdf_coi() {
+        let c = CappedCone::new(origin(), 2.0 * unit_z(), 2.0, 1.0);
+        assert_relative_eq!(c.signed_distance(2.0 * unit_x()), 0.0);
+        assert_relative_eq!(c.signed_distance(unit_y() + 2.0 * unit_z()), 0.0);
+        assert_relative_eq!(c.signed_distance(1.5 * unit_x() + unit_z()), 0.0);
+        assert_relative_eq!(c.signed_distance(2.0 * unit_z()), 0.0);
+    }
+
+    #[test]
+    fn test_cone_sdf_outside_inside() {
+        let c = CappedCone::new(origin(), 2.0 * unit_z(), 2.0, 1.0);
+        assert_relative_eq!(c.signed_distance(-unit_z()), 1.0);
+        assert_relative_eq!(c.signed_distance(0.1 * unit_z()), -0.1);
+
+        // beyond the rim the distance is to the rim itself
+        assert_relative_eq!(c.signed_distance(Vec3::new(4.0, 0.0, -1.0)), 5.0_f64.sqrt());
+    }
+
+    #[test]
+    fn test_cone_pointed() {
+        let c = CappedCone::new(origin(), unit_z(), 1.0, 0.0);
+        assert_relative_eq!(c.signed_distance(2.0 * unit_z()), 1.0);
+        assert_relative_eq!(c.signed_distance(0.5 * unit_x() + 0.5 * unit_z()), 0.0);
+    }
+
+    #[test]
+    fn test_cone_gradient() {
+        let c = CappedCone::new(origin(), 2.0 * unit_z(), 1.0, 1.0);
+        assert!(eq(c.gradient(3.0 * unit_y() + unit_z()), unit_y()));
+        assert!(eq(c.gradient(0.5 * unit_x() + 3.0 * unit_z()), unit_z()));
+        assert!(eq(c.gradient(0.1 * unit_z()), -unit_z()));
+
+        // on the axis the side is as far as the caps, so any of them will do as long as it's unit
+        assert_relative_eq!(c.gradient(unit_z()).length(), 1.0);
+    }
+}
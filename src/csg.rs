@@ -0,0 +1,294 @@
+use crate::dual::{self, Dual, Scalar};
+use crate::hittable::ImplicitSurface;
+use crate::interval::Interval;
+use crate::vec3::Vec3;
+
+// Constructive solid geometry on implicit surfaces. Each operation is itself an ImplicitSurface
+// so they nest to build up a tree, and the whole tree can be marched as a single field.
+//
+// The combined distance is a bound rather than exact (it is exact outside a union and inside an
+// intersection), which is all sphere tracing needs. The gradient is that of whichever child
+// defines the distance at v.
+
+// the largest bound of any of the children, which bounds their min or max too
+pub(crate) fn lipschitz(children: &[Box<dyn ImplicitSurface>], v: Vec3, radius: f64) -> f64 {
+    children
+        .iter()
+        .map(|c| c.lipschitz(v, radius))
+        .fold(1.0, f64::max)
+}
+
+// the children's distance intervals folded together with op, or if there aren't any empty
+pub(crate) fn fold_interval(
+    children: &[Box<dyn ImplicitSurface>],
+    v: [Interval; 3],
+    op: fn(Interval, Interval) -> Interval,
+    empty: f64,
+) -> Interval {
+    children
+        .iter()
+        .map(|c| c.signed_distance_interval(v))
+        .reduce(op)
+        .unwrap_or(Interval::point(empty))
+}
+
+// The child whose distance is extreme at v according to pick, along with that distance
+pub(crate) fn active(
+    children: &[Box<dyn ImplicitSurface>],
+    v: Vec3,
+    pick: fn(f64, f64) -> bool,
+) -> Option<(&dyn ImplicitSurface, f64)> {
+    let mut best: Option<(&dyn ImplicitSurface, f64)> = None;
+    for child in children {
+        let d = child.signed_distance(v);
+        match best {
+            Some((_, b)) if !pick(d, b) => {}
+            _ => best = Some((child.as_ref(), d)),
+        }
+    }
+    best
+}
+
+// Everything inside any of the children: min of their distances
+#[derive(Default)]
+pub struct Union {
+    children: Vec<Box<dyn ImplicitSurface>>,
+}
+
+impl Union {
+    pub fn new(a: Box<dyn ImplicitSurface>, b: Box<dyn ImplicitSurface>) -> Union {
+        Union {
+            children: vec![a, b],
+        }
+    }
+
+    pub fn from_children(children: Vec<Box<dyn ImplicitSurface>>) -> Union {
+        Union { children }
+    }
+
+    pub fn add(&mut self, child: Box<dyn ImplicitSurface>) {
+        self.children.push(child);
+    }
+}
+
+impl ImplicitSurface for Union {
+    // an empty union contains nothing, so everywhere is infinitely far outside it
+    fn signed_distance(&self, v: Vec3) -> f64 {
+        active(&self.children, v, |d, b| d < b).map_or(f64::INFINITY, |(_, d)| d)
+    }
+
+    fn signed_distance_dual(&self, v: [Dual; 3]) -> Dual {
+        active(&self.children, dual::value(v), |d, b| d < b)
+            .map_or(Dual::constant(f64::INFINITY), |(c, _)| {
+                c.signed_distance_dual(v)
+            })
+    }
+
+    fn lipschitz(&self, v: Vec3, radius: f64) -> f64 {
+        lipschitz(&self.children, v, radius)
+    }
+
+    fn signed_distance_interval(&self, v: [Interval; 3]) -> Interval {
+        fold_interval(&self.children, v, Scalar::min, f64::INFINITY)
+    }
+}
+
+// Everything inside all of the children: max of their distances
+#[derive(Default)]
+pub struct Intersection {
+    children: Vec<Box<dyn ImplicitSurface>>,
+}
+
+impl Intersection {
+    pub fn new(a: Box<dyn ImplicitSurface>, b: Box<dyn ImplicitSurface>) -> Intersection {
+        Intersection {
+            children: vec![a, b],
+        }
+    }
+
+    pub fn from_children(children: Vec<Box<dyn ImplicitSurface>>) -> Intersection {
+        Intersection { children }
+    }
+
+    pub fn add(&mut self, child: Box<dyn ImplicitSurface>) {
+        self.children.push(child);
+    }
+}
+
+impl ImplicitSurface for Intersection {
+    // an empty intersection is all of space, so everywhere is infinitely far inside it
+    fn signed_distance(&self, v: Vec3) -> f64 {
+        active(&self.children, v, |d, b| d > b).map_or(f64::NEG_INFINITY, |(_, d)| d)
+    }
+
+    fn signed_distance_dual(&self, v: [Dual; 3]) -> Dual {
+        active(&self.children, dual::value(v), |d, b| d > b)
+            .map_or(Dual::constant(f64::NEG_INFINITY), |(c, _)| {
+                c.signed_distance_dual(v)
+            })
+    }
+
+    fn lipschitz(&self, v: Vec3, radius: f64) -> f64 {
+        lipschitz(&self.children, v, radius)
+    }
+
+    fn signed_distance_interval(&self, v: [Interval; 3]) -> Interval {
+        fold_interval(&self.children, v, Scalar::max, f64::NEG_INFINITY)
+    }
+}
+
+// Everything inside the base but outside all of the tools cut from it. Cutting is intersecting
+// with the complement, whose distance is the negated distance of the tool: max(a, -b).
+pub struct Difference {
+    base: Box<dyn ImplicitSurface>,
+    tools: Union,
+}
+
+impl Difference {
+    pub fn new(base: Box<dyn ImplicitSurface>, tool: Box<dyn ImplicitSurface>) -> Difference {
+        Difference::from_children(base, vec![tool])
+    }
+
+    pub fn from_children(
+        base: Box<dyn ImplicitSurface>,
+        tools: Vec<Box<dyn ImplicitSurface>>,
+    ) -> Difference {
+        Difference {
+            base,
+            tools: Union::from_children(tools),
+        }
+    }
+
+    pub fn add(&mut self, tool: Box<dyn ImplicitSurface>) {
+        self.tools.add(tool);
+    }
+}
+
+impl ImplicitSurface for Difference {
+    fn signed_distance(&self, v: Vec3) -> f64 {
+        f64::max(self.base.signed_distance(v), -self.tools.signed_distance(v))
+    }
+
+    fn signed_distance_dual(&self, v: [Dual; 3]) -> Dual {
+        Scalar::max(
+            self.base.signed_distance_dual(v),
+            -self.tools.signed_distance_dual(v),
+        )
+    }
+
+    fn lipschitz(&self, v: Vec3, radius: f64) -> f64 {
+        f64::max(
+            self.base.lipschitz(v, radius),
+            self.tools.lipschitz(v, radius),
+        )
+    }
+
+    fn signed_distance_interval(&self, v: [Interval; 3]) -> Interval {
+        Scalar::max(
+            self.base.signed_distance_interval(v),
+            -self.tools.signed_distance_interval(v),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::ray::{MarchSettings, Ray};
+    use crate::sphere::Sphere;
+    use crate::vec3::{eq, normalise, origin, unit_x, unit_y};
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    // unit spheres at x = -0.5 and x = 0.5, overlapping around the origin
+    fn pair() -> (Box<dyn ImplicitSurface>, Box<dyn ImplicitSurface>) {
+        (
+            Box::new(Sphere::new(-0.5 * unit_x(), 1.0)),
+            Box::new(Sphere::new(0.5 * unit_x(), 1.0)),
+        )
+    }
+
+    #[test]
+    fn test_union_sdf() {
+        let (a, b) = pair();
+        let u = Union::new(a, b);
+        assert_relative_eq!(u.signed_distance(1.5 * unit_x()), 0.0);
+        assert_relative_eq!(u.signed_distance(-1.5 * unit_x()), 0.0);
+        assert_relative_eq!(u.signed_distance(3.0 * unit_x()), 1.5);
+        assert_relative_eq!(u.signed_distance(0.5 * unit_x()), -1.0);
+
+        assert!(eq(normalise(u.gradient(3.0 * unit_x())), unit_x()));
+        assert!(eq(normalise(u.gradient(-3.0 * unit_x())), -unit_x()));
+    }
+
+    #[test]
+    fn test_intersection_sdf() {
+        let (a, b) = pair();
+        let i = Intersection::new(a, b);
+        assert_relative_eq!(i.signed_distance(0.5 * unit_x()), 0.0);
+        assert_relative_eq!(i.signed_distance(-0.5 * unit_x()), 0.0);
+        assert_relative_eq!(i.signed_distance(origin()), -0.5);
+        assert_relative_eq!(i.signed_distance(1.5 * unit_x()), 1.0);
+
+        // at x = 0.5 we're on the surface of the sphere to the left
+        assert!(eq(normalise(i.gradient(0.5 * unit_x())), unit_x()));
+    }
+
+    #[test]
+    fn test_difference_sdf() {
+        let (a, b) = pair();
+        let d = Difference::new(a, b);
+        assert_relative_eq!(d.signed_distance(-1.5 * unit_x()), 0.0);
+        assert_relative_eq!(d.signed_distance(-0.5 * unit_x()), 0.0);
+        assert_relative_eq!(d.signed_distance(-1.0 * unit_x()), -0.5);
+        assert_relative_eq!(d.signed_distance(unit_x()), 0.5);
+
+        // the bite taken out of the base faces back towards the tool
+        assert!(eq(normalise(d.gradient(-0.4 * unit_x())), unit_x()));
+        assert!(eq(normalise(d.gradient(-1.4 * unit_x())), -unit_x()));
+    }
+
+    #[test]
+    fn test_n_ary() {
+        let mut u = Union::default();
+        assert_eq!(u.signed_distance(origin()), f64::INFINITY);
+
+        for i in 0..4 {
+            u.add(Box::new(Sphere::new(3.0 * i as f64 * unit_x(), 1.0)));
+        }
+        assert_relative_eq!(u.signed_distance(10.0 * unit_x()), 0.0);
+        assert_relative_eq!(u.signed_distance(4.5 * unit_x()), 0.5);
+
+        let mut d = Difference::new(Box::new(u), Box::new(Sphere::new(origin(), 0.5)));
+        d.add(Box::new(Sphere::new(3.0 * unit_x(), 0.5)));
+        assert_relative_eq!(d.signed_distance(origin()), 0.5);
+        assert_relative_eq!(d.signed_distance(3.0 * unit_x()), 0.5);
+        assert_relative_eq!(d.signed_distance(6.0 * unit_x()), -1.0);
+    }
+
+    #[test]
+    fn test_trace_difference() {
+        // a unit sphere with a hole drilled through it along x
+        let d = Difference::new(
+            Box::new(Sphere::new(origin(), 1.0)),
+            Box::new(crate::cylinder::Cylinder::new(origin(), unit_x(), 0.5)),
+        );
+
+        // firing down the hole we pass straight through
+        let r = Ray::new(-5.0 * unit_x(), unit_x());
+        assert!(r
+            .trace(&d, 0.0, 10.0, &MarchSettings::default())
+            .record
+            .is_none());
+
+        // but off to the side we hit the sphere itself
+        let r = Ray::new(-5.0 * unit_x() + 0.75 * unit_y(), unit_x());
+        let rec = r
+            .trace(&d, 0.0, 10.0, &MarchSettings::default())
+            .record
+            .unwrap();
+        assert_relative_eq!(rec.p.x(), -f64::sqrt(1.0 - 0.75 * 0.75), epsilon = 1e-6);
+    }
+}
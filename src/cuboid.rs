@@ -0,0 +1,127 @@
+use crate::dual::{self, Dual, Scalar};
+use crate::hittable::ImplicitSurface;
+use crate::vec3::{Point3, Vec3};
+
+// An axis-aligned box. Named to stay clear of std's Box which we use everywhere for trait objects.
+#[derive(Copy, Clone, Default)]
+pub struct Cuboid {
+    center: Point3,
+    half_extents: Vec3,
+}
+
+impl Cuboid {
+    pub fn new(center: Point3, half_extents: Vec3) -> Cuboid {
+        Cuboid {
+            center,
+            half_extents,
+        }
+    }
+
+    // Fold v into the positive octant, then q is how far past each face it lies. Outside the
+    // distance is the length of the positive part of q, and inside it is the closest face.
+    fn distance<T: Scalar>(&self, v: [T; 3]) -> T {
+        let p = dual::offset(v, self.center);
+        let q = dual::offset(p.map(|x| x.abs()), self.half_extents);
+
+        let outside = dual::length(q.map(|x| x.max(T::constant(0.0))));
+        let inside = q[0].max(q[1]).max(q[2]).min(T::constant(0.0));
+        outside + inside
+    }
+}
+
+impl ImplicitSurface for Cuboid {
+    fn signed_distance(&self, v: Vec3) -> f64 {
+        self.distance(dual::constant(v))
+    }
+
+    fn signed_distance_dual(&self, v: [Dual; 3]) -> Dual {
+        self.distance(v)
+    }
+}
+
+// An axis-aligned box whose edges and corners are rounded off with the given radius. The half
+// extents are those of the whole solid, including the rounding.
+#[derive(Copy, Clone, Default)]
+pub struct RoundedCuboid {
+    core: Cuboid,
+    radius: f64,
+}
+
+impl RoundedCuboid {
+    pub fn new(center: Point3, half_extents: Vec3, radius: f64) -> RoundedCuboid {
+        let r = Vec3::new(radius, radius, radius);
+        RoundedCuboid {
+            core: Cuboid::new(center, half_extents - r),
+            radius,
+        }
+    }
+}
+
+impl ImplicitSurface for RoundedCuboid {
+    // rounding is an offset of a smaller box by the radius
+    fn signed_distance(&self, v: Vec3) -> f64 {
+        self.core.signed_distance(v) - self.radius
+    }
+
+    fn signed_distance_dual(&self, v: [Dual; 3]) -> Dual {
+        self.core.signed_distance_dual(v) - self.radius
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vec3::{eq, normalise, origin, unit_x, unit_y, unit_z};
+    use approx::assert_relative_eq;
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_cuboid_sdf_coi() {
+        let b = Cuboid::new(origin(), Vec3::new(1.0, 2.0, 3.0));
+        assert_relative_eq!(b.signed_distance(unit_x()), 0.0);
+        assert_relative_eq!(b.signed_distance(2.0 * unit_y()), 0.0);
+        assert_relative_eq!(b.signed_distance(-3.0 * unit_z()), 0.0);
+        assert_relative_eq!(b.signed_distance(Vec3::new(1.0, 2.0, 3.0)), 0.0);
+    }
+
+    #[test]
+    fn test_cuboid_sdf_outside_inside() {
+        let b = Cuboid::new(origin(), Vec3::new(1.0, 2.0, 3.0));
+        assert_relative_eq!(b.signed_distance(origin()), -1.0);
+        assert_relative_eq!(b.signed_distance(3.0 * unit_x()), 2.0);
+
+        // off a corner the distance is to the corner itself
+        assert_relative_eq!(b.signed_distance(Vec3::new(2.0, 3.0, 3.0)), 2.0_f64.sqrt());
+    }
+
+    #[test]
+    fn test_cuboid_gradient() {
+        let b = Cuboid::new(origin(), Vec3::new(1.0, 2.0, 3.0));
+        assert!(eq(b.gradient(-3.0 * unit_x()), -unit_x()));
+        assert!(eq(b.gradient(0.5 * unit_x()), unit_x()));
+        assert!(eq(b.gradient(Vec3::new(0.0, 1.9, 0.0)), unit_y()));
+        assert!(eq(
+            b.gradient(Vec3::new(2.0, -3.0, 0.0)),
+            normalise(Vec3::new(1.0, -1.0, 0.0))
+        ));
+    }
+
+    #[test]
+    fn test_rounded_cuboid_sdf() {
+        let b = RoundedCuboid::new(origin(), Vec3::new(1.0, 1.0, 1.0), 0.25);
+
+        // faces are where they would be without rounding
+        assert_relative_eq!(b.signed_distance(unit_x()), 0.0);
+        assert_relative_eq!(b.signed_distance(origin()), -1.0);
+
+        // but the corner is pulled in
+        let corner = Vec3::new(1.0, 1.0, 1.0);
+        assert_relative_eq!(
+            b.signed_distance(corner),
+            (0.25 * corner).length() - 0.25,
+            epsilon = 1e-12
+        );
+        assert!(eq(b.gradient(corner), normalise(corner)));
+    }
+}
@@ -1,5 +1,7 @@
+use crate::dual::{self, Dual, Scalar};
 use crate::hittable::ImplicitSurface;
-use crate::vec3::{self, cross, normalise, Point3, Vec3};
+use crate::profile;
+use crate::vec3::{dot, normalise, perpendicular, Point3, Vec3};
 
 #[derive(Copy, Clone, Default)]
 pub struct Cylinder {
@@ -16,30 +18,96 @@ impl Cylinder {
             radius: r,
         }
     }
-}
 
-impl ImplicitSurface for Cylinder {
     // signed distance function for a cylinder is the closest distance to its spine minus its radius
     //
     // d = (| v - p | X d ) - r
     //
+    fn distance<T: Scalar>(&self, v: [T; 3]) -> T {
+        dual::length(dual::cross(dual::offset(v, self.p), self.dir)) - self.radius
+    }
+}
+
+impl ImplicitSurface for Cylinder {
     fn signed_distance(&self, v: Vec3) -> f64 {
-        cross(v - self.p, self.dir).length() - self.radius
+        self.distance(dual::constant(v))
+    }
+
+    fn signed_distance_dual(&self, v: [Dual; 3]) -> Dual {
+        self.distance(v)
     }
 
     // if q is the closet point to the cylinder spine from v, then v - p is the gradient
     fn gradient(&self, v: Vec3) -> Vec3 {
-        let l = cross(v - self.p, self.dir).length();
-        let h = (v - self.p).length();
-        let closest_point = self.p + (self.dir * f64::sqrt((h * h) - (l * l)));
+        // along the spine by the signed projection, as a length found by Pythagoras would put
+        // the closest point on the wrong side of p for points behind it
+        let closest_point = self.p + (self.dir * dot(v - self.p, self.dir));
 
         v - closest_point
     }
 }
 
+// A finite cylinder running from a to b, closed off with flat end caps
+#[derive(Copy, Clone, Default)]
+pub struct CappedCylinder {
+    a: Point3,
+    axis: Vec3,
+    height: f64,
+    radius: f64,
+}
+
+impl CappedCylinder {
+    pub fn new(a: Point3, b: Point3, r: f64) -> CappedCylinder {
+        CappedCylinder {
+            a,
+            axis: normalise(b - a),
+            height: (b - a).length(),
+            radius: r,
+        }
+    }
+
+    fn distance_and_gradient(&self, v: Vec3) -> (f64, Vec3) {
+        let q = v - self.a;
+        let y = dot(q, self.axis);
+        let radial = q - y * self.axis;
+
+        let rho = radial.length();
+        let dir = if rho > 0.0 {
+            radial / rho
+        } else {
+            perpendicular(self.axis)
+        };
+
+        // distance to the pair of cap planes, measured from half way along the spine
+        let mid = y - 0.5 * self.height;
+        let dy = mid.abs() - 0.5 * self.height;
+        let gy = if mid < 0.0 { -self.axis } else { self.axis };
+
+        profile::extrude(rho - self.radius, dir, dy, gy)
+    }
+}
+
+impl ImplicitSurface for CappedCylinder {
+    // The infinite cylinder's distance combined with the distance to the caps. Outside both the
+    // closest point is on the rim, otherwise it is on whichever of the side or caps is closer.
+    fn signed_distance(&self, v: Vec3) -> f64 {
+        self.distance_and_gradient(v).0
+    }
+
+    fn gradient(&self, v: Vec3) -> Vec3 {
+        self.distance_and_gradient(v).1
+    }
+
+    fn signed_distance_dual(&self, v: [Dual; 3]) -> Dual {
+        let (d, g) = self.distance_and_gradient(dual::value(v));
+        dual::chain(v, d, g)
+    }
+}
+
+#[cfg(test)]
 mod tests {
+    use crate::vec3::{eq, origin, unit_x, unit_y, unit_z};
     use approx::assert_relative_eq;
-    use vec3::{origin, unit_x, unit_y, unit_z};
 
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
@@ -74,4 +142,61 @@ mod tests {
         assert_relative_eq!(v.y(), 0.0);
         assert_relative_eq!(v.z(), 0.0);
     }
+
+    #[test]
+    fn test_capped_cylinder_sdf_coi() {
+        let s = CappedCylinder::new(origin(), 2.0 * unit_z(), 1.0);
+        assert_relative_eq!(s.signed_distance(unit_x() + unit_z()), 0.0);
+        assert_relative_eq!(s.signed_distance(0.5 * unit_y()), 0.0);
+        assert_relative_eq!(s.signed_distance(2.0 * unit_z()), 0.0);
+        assert_relative_eq!(s.signed_distance(unit_x() + 2.0 * unit_z()), 0.0);
+    }
+
+    #[test]
+    fn test_capped_cylinder_external_internal() {
+        let s = CappedCylinder::new(origin(), 2.0 * unit_z(), 1.0);
+        assert_relative_eq!(s.signed_distance(unit_z()), -1.0);
+        assert_relative_eq!(s.signed_distance(0.5 * unit_x() + 0.25 * unit_z()), -0.25);
+        assert_relative_eq!(s.signed_distance(5.0 * unit_z()), 3.0);
+        assert_relative_eq!(s.signed_distance(3.0 * unit_y() + unit_z()), 2.0);
+
+        // beyond the rim the distance is to the rim itself
+        assert_relative_eq!(s.signed_distance(2.0 * unit_x() - unit_z()), 2.0_f64.sqrt());
+    }
+
+    #[test]
+    fn test_capped_cylinder_gradient() {
+        let s = CappedCylinder::new(origin(), 2.0 * unit_z(), 1.0);
+
+        // flat on the ends, both on and off the axis
+        assert!(eq(s.gradient(3.0 * unit_z()), unit_z()));
+        assert!(eq(s.gradient(0.5 * unit_y() - unit_z()), -unit_z()));
+        assert!(eq(s.gradient(0.5 * unit_x() + 1.9 * unit_z()), unit_z()));
+
+        // radial on the side
+        assert!(eq(s.gradient(3.0 * unit_y() + unit_z()), unit_y()));
+        assert!(eq(s.gradient(0.9 * unit_x() + unit_z()), unit_x()));
+
+        // and between the two around the rim
+        assert!(eq(
+            s.gradient(2.0 * unit_x() - unit_z()),
+            normalise(unit_x() - unit_z())
+        ));
+    }
+
+    #[test]
+    fn test_capped_cylinder_tilted() {
+        // running along x + y rather than an axis
+        let s = CappedCylinder::new(origin(), Vec3::new(3.0, 3.0, 0.0), 0.5);
+        let end = Vec3::new(3.0, 3.0, 0.0);
+        let axis = normalise(end);
+
+        assert_relative_eq!(s.signed_distance(end + axis), 1.0, epsilon = 1e-12);
+        assert_relative_eq!(
+            s.signed_distance(0.5 * end + unit_z()),
+            0.5,
+            epsilon = 1e-12
+        );
+        assert!(eq(s.gradient(end + axis), axis));
+    }
 }
@@ -0,0 +1,136 @@
+use std::fmt;
+
+use crate::ray::{Outcome, Trace};
+
+// How the traces of a render ended, so a genuine miss can be told apart from the tracer failing,
+// e.g. holes from running out of steps or a field that isn't a distance causing divergence
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Diagnostics {
+    pub hits: usize,
+    pub misses: usize,
+    pub exhausted: usize,
+    pub diverged: usize,
+    pub started_on_surface: usize,
+    // steps taken over all the traces
+    pub steps: usize,
+    // the largest |distance| at which a trace ran out of steps, if any did. Small means it was
+    // creeping along close to the surface, e.g. grazing it.
+    pub worst_distance: f64,
+}
+
+impl Diagnostics {
+    pub fn record(&mut self, trace: &Trace) {
+        match trace.outcome {
+            Outcome::Hit => self.hits += 1,
+            Outcome::Miss => self.misses += 1,
+            Outcome::Exhausted => self.exhausted += 1,
+            Outcome::Diverged => self.diverged += 1,
+            Outcome::StartedOnSurface => self.started_on_surface += 1,
+        }
+        self.steps += trace.steps;
+
+        if trace.outcome == Outcome::Exhausted {
+            self.worst_distance = self.worst_distance.max(trace.distance.abs());
+        }
+    }
+
+    pub fn traces(&self) -> usize {
+        self.hits + self.misses + self.exhausted + self.diverged + self.started_on_surface
+    }
+
+    // traces that ended without knowing whether the ray hits
+    pub fn failures(&self) -> usize {
+        self.exhausted + self.diverged
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let traces = self.traces();
+        let percent = |n: usize| 100.0 * n as f64 / traces.max(1) as f64;
+
+        writeln!(
+            f,
+            "{} traces of implicit surfaces, {:.1} steps on average",
+            traces,
+            self.steps as f64 / traces.max(1) as f64
+        )?;
+        for (what, n) in [
+            ("hit", self.hits),
+            ("missed", self.misses),
+            ("ran out of steps", self.exhausted),
+            ("diverged", self.diverged),
+            ("started on the surface", self.started_on_surface),
+        ] {
+            writeln!(f, "  {:<24}{:>10} ({:.2}%)", what, n, percent(n))?;
+        }
+        if self.exhausted > 0 {
+            writeln!(
+                f,
+                "  traces that ran out of steps stopped at most {:.3e} from the surface",
+                self.worst_distance
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::equation::Equation;
+    use crate::plane::Plane;
+    use crate::ray::{MarchSettings, Ray};
+    use crate::vec3::{origin, unit_x, unit_y, Vec3};
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_diagnostics_outcomes() {
+        let mut diagnostics = Diagnostics::default();
+        let march = MarchSettings::default();
+        let floor = Plane::new(-unit_y(), unit_y());
+
+        // grazing the floor, two steps aren't enough to get there
+        let short = MarchSettings {
+            max_steps: 2,
+            ..Default::default()
+        };
+        let r = Ray::new(origin(), Vec3::new(1.0, -0.2, 0.0));
+        let trace = r.trace(&floor, 0.0, 10.0, &short);
+        assert_eq!(trace.outcome, Outcome::Exhausted);
+        diagnostics.record(&trace);
+
+        // a field that grows much faster than the distance, without saying so, steps right past
+        // the sphere and finds itself further away
+        let f = Equation::parse("(x - 5)^2 + y^2 + z^2 - 1", &HashMap::new()).unwrap();
+        let trace = Ray::new(origin(), unit_x()).trace(&f, 0.0, 100.0, &march);
+        assert_eq!(trace.outcome, Outcome::Diverged);
+        diagnostics.record(&trace);
+
+        // a ray along the floor never leaves it
+        let trace = Ray::new(-unit_y(), unit_x()).trace(&floor, 0.0, 10.0, &march);
+        assert_eq!(trace.outcome, Outcome::StartedOnSurface);
+        diagnostics.record(&trace);
+
+        // and one that plainly hits it
+        diagnostics.record(&Ray::new(origin(), -unit_y()).trace(&floor, 0.0, 10.0, &march));
+
+        assert_eq!(diagnostics.traces(), 4);
+        assert_eq!(diagnostics.hits, 1);
+        assert_eq!(diagnostics.misses, 0);
+        assert_eq!(diagnostics.exhausted, 1);
+        assert_eq!(diagnostics.diverged, 1);
+        assert_eq!(diagnostics.started_on_surface, 1);
+        // starting on the surface is a result rather than a failure
+        assert_eq!(diagnostics.failures(), 2);
+        // the grazing ray stopped well short of the floor
+        assert!(diagnostics.worst_distance > 0.1);
+
+        let report = diagnostics.to_string();
+        assert!(report.starts_with("4 traces of implicit surfaces"));
+        assert!(report.contains("ran out of steps"));
+    }
+}
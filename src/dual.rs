@@ -0,0 +1,422 @@
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use crate::vec3::{unit_x, unit_y, unit_z, Mat4, Point3, Vec3};
+
+// Forward-mode automatic differentiation. A Dual is a value together with its gradient with
+// respect to the point a surface is being evaluated at, and every operation on it applies the
+// chain rule. A distance function written once against Scalar can then be run on f64 to get the
+// distance, or on Dual to get the distance and its exact gradient in a single pass.
+//
+// Points are [T; 3] rather than Vec3 so they can hold either. Only the operations distance
+// functions need are here, mostly combining a point with a constant Vec3 such as a centre or axis.
+
+pub trait Scalar:
+    Copy
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + Add<f64, Output = Self>
+    + Sub<f64, Output = Self>
+    + Mul<f64, Output = Self>
+    + Div<f64, Output = Self>
+{
+    fn constant(c: f64) -> Self;
+    fn value(self) -> f64;
+
+    fn sqrt(self) -> Self;
+    fn abs(self) -> Self;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn tan(self) -> Self;
+    fn asin(self) -> Self;
+    fn acos(self) -> Self;
+    fn atan(self) -> Self;
+    fn exp(self) -> Self;
+    fn ln(self) -> Self;
+    fn powf(self, n: f64) -> Self;
+
+    fn pow(self, e: Self) -> Self {
+        (e * self.ln()).exp()
+    }
+
+    // ties go to self, which matters for which side's derivative we take
+    fn min(self, other: Self) -> Self {
+        if other.value() < self.value() {
+            other
+        } else {
+            self
+        }
+    }
+
+    fn max(self, other: Self) -> Self {
+        if other.value() > self.value() {
+            other
+        } else {
+            self
+        }
+    }
+
+    fn clamp(self, lo: f64, hi: f64) -> Self {
+        self.max(Self::constant(lo)).min(Self::constant(hi))
+    }
+
+    // -1, 0 or 1, which is flat so carries no derivative
+    fn sign(self) -> Self {
+        let v = self.value();
+        Self::constant(if v < 0.0 {
+            -1.0
+        } else if v > 0.0 {
+            1.0
+        } else {
+            0.0
+        })
+    }
+
+    // if self < other then a else b
+    fn select(self, other: Self, a: Self, b: Self) -> Self {
+        if self.value() < other.value() {
+            a
+        } else {
+            b
+        }
+    }
+}
+
+impl Scalar for f64 {
+    fn constant(c: f64) -> f64 {
+        c
+    }
+
+    fn value(self) -> f64 {
+        self
+    }
+
+    fn sqrt(self) -> f64 {
+        f64::sqrt(self)
+    }
+
+    fn abs(self) -> f64 {
+        f64::abs(self)
+    }
+
+    fn sin(self) -> f64 {
+        f64::sin(self)
+    }
+
+    fn cos(self) -> f64 {
+        f64::cos(self)
+    }
+
+    fn tan(self) -> f64 {
+        f64::tan(self)
+    }
+
+    fn asin(self) -> f64 {
+        f64::asin(self)
+    }
+
+    fn acos(self) -> f64 {
+        f64::acos(self)
+    }
+
+    fn atan(self) -> f64 {
+        f64::atan(self)
+    }
+
+    fn exp(self) -> f64 {
+        f64::exp(self)
+    }
+
+    fn ln(self) -> f64 {
+        f64::ln(self)
+    }
+
+    fn powf(self, n: f64) -> f64 {
+        f64::powf(self, n)
+    }
+
+    fn pow(self, e: f64) -> f64 {
+        f64::powf(self, e)
+    }
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct Dual {
+    pub value: f64,
+    pub grad: Vec3,
+}
+
+impl Dual {
+    pub fn new(value: f64, grad: Vec3) -> Dual {
+        Dual { value, grad }
+    }
+
+    // the coordinates of v, each seeded with its own unit derivative, to differentiate with
+    // respect to v
+    pub fn variables(v: Vec3) -> [Dual; 3] {
+        [
+            Dual::new(v.x(), unit_x()),
+            Dual::new(v.y(), unit_y()),
+            Dual::new(v.z(), unit_z()),
+        ]
+    }
+
+    // apply a function with value f and derivative df at self
+    fn chain(self, f: f64, df: f64) -> Dual {
+        Dual::new(f, df * self.grad)
+    }
+}
+
+impl Scalar for Dual {
+    fn constant(c: f64) -> Dual {
+        Dual::new(c, Vec3::default())
+    }
+
+    fn value(self) -> f64 {
+        self.value
+    }
+
+    // At zero the derivative is infinite, e.g. the length of a vector at the origin. We take it
+    // to be zero there rather than let NaNs through.
+    fn sqrt(self) -> Dual {
+        let s = self.value.sqrt();
+        self.chain(s, if s > 0.0 { 0.5 / s } else { 0.0 })
+    }
+
+    fn abs(self) -> Dual {
+        if self.value < 0.0 {
+            -self
+        } else {
+            self
+        }
+    }
+
+    fn sin(self) -> Dual {
+        self.chain(self.value.sin(), self.value.cos())
+    }
+
+    fn cos(self) -> Dual {
+        self.chain(self.value.cos(), -self.value.sin())
+    }
+
+    fn tan(self) -> Dual {
+        let c = self.value.cos();
+        self.chain(self.value.tan(), 1.0 / (c * c))
+    }
+
+    fn asin(self) -> Dual {
+        let x = self.value;
+        self.chain(x.asin(), 1.0 / (1.0 - x * x).sqrt())
+    }
+
+    fn acos(self) -> Dual {
+        let x = self.value;
+        self.chain(x.acos(), -1.0 / (1.0 - x * x).sqrt())
+    }
+
+    fn atan(self) -> Dual {
+        let x = self.value;
+        self.chain(x.atan(), 1.0 / (1.0 + x * x))
+    }
+
+    fn exp(self) -> Dual {
+        let e = self.value.exp();
+        self.chain(e, e)
+    }
+
+    fn ln(self) -> Dual {
+        self.chain(self.value.ln(), 1.0 / self.value)
+    }
+
+    fn powf(self, n: f64) -> Dual {
+        let x = self.value;
+        self.chain(x.powf(n), n * x.powf(n - 1.0))
+    }
+}
+
+impl Neg for Dual {
+    type Output = Dual;
+    fn neg(self) -> Dual {
+        Dual::new(-self.value, -self.grad)
+    }
+}
+
+impl Add for Dual {
+    type Output = Dual;
+    fn add(self, o: Dual) -> Dual {
+        Dual::new(self.value + o.value, self.grad + o.grad)
+    }
+}
+
+impl Sub for Dual {
+    type Output = Dual;
+    fn sub(self, o: Dual) -> Dual {
+        Dual::new(self.value - o.value, self.grad - o.grad)
+    }
+}
+
+impl Mul for Dual {
+    type Output = Dual;
+    fn mul(self, o: Dual) -> Dual {
+        Dual::new(
+            self.value * o.value,
+            o.value * self.grad + self.value * o.grad,
+        )
+    }
+}
+
+impl Div for Dual {
+    type Output = Dual;
+    fn div(self, o: Dual) -> Dual {
+        Dual::new(
+            self.value / o.value,
+            (o.value * self.grad - self.value * o.grad) / (o.value * o.value),
+        )
+    }
+}
+
+impl Add<f64> for Dual {
+    type Output = Dual;
+    fn add(self, c: f64) -> Dual {
+        Dual::new(self.value + c, self.grad)
+    }
+}
+
+impl Sub<f64> for Dual {
+    type Output = Dual;
+    fn sub(self, c: f64) -> Dual {
+        Dual::new(self.value - c, self.grad)
+    }
+}
+
+impl Mul<f64> for Dual {
+    type Output = Dual;
+    fn mul(self, c: f64) -> Dual {
+        Dual::new(self.value * c, c * self.grad)
+    }
+}
+
+impl Div<f64> for Dual {
+    type Output = Dual;
+    fn div(self, c: f64) -> Dual {
+        Dual::new(self.value / c, self.grad / c)
+    }
+}
+
+// Points
+
+pub fn constant<T: Scalar>(v: Vec3) -> [T; 3] {
+    [T::constant(v.x()), T::constant(v.y()), T::constant(v.z())]
+}
+
+pub fn value<T: Scalar>(p: [T; 3]) -> Point3 {
+    Vec3::new(p[0].value(), p[1].value(), p[2].value())
+}
+
+pub fn add<T: Scalar>(p: [T; 3], q: [T; 3]) -> [T; 3] {
+    [p[0] + q[0], p[1] + q[1], p[2] + q[2]]
+}
+
+pub fn sub<T: Scalar>(p: [T; 3], q: [T; 3]) -> [T; 3] {
+    [p[0] - q[0], p[1] - q[1], p[2] - q[2]]
+}
+
+// p less a constant vector
+pub fn offset<T: Scalar>(p: [T; 3], v: Vec3) -> [T; 3] {
+    [p[0] - v.x(), p[1] - v.y(), p[2] - v.z()]
+}
+
+// a constant direction scaled by t
+pub fn along<T: Scalar>(t: T, v: Vec3) -> [T; 3] {
+    [t * v.x(), t * v.y(), t * v.z()]
+}
+
+pub fn dot<T: Scalar>(p: [T; 3], v: Vec3) -> T {
+    p[0] * v.x() + p[1] * v.y() + p[2] * v.z()
+}
+
+pub fn cross<T: Scalar>(p: [T; 3], v: Vec3) -> [T; 3] {
+    [
+        p[1] * v.z() - p[2] * v.y(),
+        p[2] * v.x() - p[0] * v.z(),
+        p[0] * v.y() - p[1] * v.x(),
+    ]
+}
+
+pub fn length<T: Scalar>(p: [T; 3]) -> T {
+    (p[0] * p[0] + p[1] * p[1] + p[2] * p[2]).sqrt()
+}
+
+// A function with value d and gradient g at the value of p, carrying on p's own derivatives. This
+// is the chain rule for surfaces that find their gradient some other way.
+pub fn chain(p: [Dual; 3], d: f64, g: Vec3) -> Dual {
+    Dual::new(d, g.x() * p[0].grad + g.y() * p[1].grad + g.z() * p[2].grad)
+}
+
+pub fn transform_point<T: Scalar>(m: &Mat4, p: [T; 3]) -> [T; 3] {
+    let row = |r: usize| p[0] * m.get(r, 0) + p[1] * m.get(r, 1) + p[2] * m.get(r, 2) + m.get(r, 3);
+    [row(0), row(1), row(2)]
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::vec3::eq;
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_dual_arithmetic() {
+        let [x, y, z] = Dual::variables(Vec3::new(2.0, 3.0, 5.0));
+
+        // f = x y / z + x^2, so df = (y / z + 2x, x / z, -x y / z^2)
+        let f = x * y / z + x.powf(2.0);
+        assert_relative_eq!(f.value, 5.2);
+        assert!(eq(f.grad, Vec3::new(4.6, 0.4, -0.24)));
+
+        // constants carry no derivative
+        let g = (x - 1.0) * 3.0 + Dual::constant(7.0);
+        assert!(eq(g.grad, Vec3::new(3.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_dual_functions() {
+        let h = 1e-6;
+        let fs: [fn(Dual) -> Dual; 9] = [
+            |x| x.sqrt(),
+            |x| x.sin(),
+            |x| x.cos(),
+            |x| x.tan(),
+            |x| x.asin(),
+            |x| x.acos(),
+            |x| x.atan(),
+            |x| x.exp() * x.ln(),
+            |x| x.pow(x),
+        ];
+
+        for f in fs {
+            let x = 0.4;
+            let d = f(Dual::new(x, unit_x())).grad.x();
+            let fd = (f(Dual::constant(x + h)).value - f(Dual::constant(x - h)).value) / (2.0 * h);
+            assert_relative_eq!(d, fd, epsilon = 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_dual_length() {
+        let p = Vec3::new(3.0, 0.0, 4.0);
+        let l = length(Dual::variables(p));
+        assert_relative_eq!(l.value, 5.0);
+        assert!(eq(l.grad, p / 5.0));
+
+        // zero at the origin rather than NaN
+        assert!(eq(
+            length(Dual::variables(Vec3::default())).grad,
+            Vec3::default()
+        ));
+    }
+}
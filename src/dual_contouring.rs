@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+
+use crate::grid::{SampleGrid, EDGES};
+use crate::hittable::ImplicitSurface;
+use crate::mesh::Mesh;
+use crate::octree::Octree;
+use crate::qef::Qef;
+use crate::vec3::{normalise, unit_x, unit_y, unit_z, Point3, Vec3};
+
+// bisection steps used to pin down where the surface crosses a cell edge
+const CROSSING_STEPS: usize = 16;
+
+// Polygonize the zero set of a surface within the box min-max using dual contouring (Ju et al.,
+// "Dual Contouring of Hermite Data"). Resolution is the number of cells along the longest side of
+// the box.
+//
+// Where marching cubes puts vertices on cell edges, dual contouring puts one inside each cell the
+// surface passes through, at the point that best fits the tangent planes at the cell's edge
+// crossings. The planes come from the analytic gradient, so at a crease or corner the vertex lands
+// on the feature itself rather than cutting across it. Each grid edge the surface crosses then
+// becomes a quad joining the vertices of the four cells around it, split into two triangles.
+pub fn polygonize(
+    surface: &dyn ImplicitSurface,
+    min: Point3,
+    max: Point3,
+    resolution: usize,
+) -> Mesh {
+    contour(surface, &SampleGrid::new(surface, min, max, resolution))
+}
+
+// As polygonize, but only visiting the leaves of an octree rather than every cell of the box
+pub fn polygonize_octree(surface: &dyn ImplicitSurface, tree: &Octree) -> Mesh {
+    contour(surface, &SampleGrid::from_octree(surface, tree))
+}
+
+fn contour(surface: &dyn ImplicitSurface, grid: &SampleGrid) -> Mesh {
+    let mut mesh = Mesh::new();
+    let mut cell_vertex: HashMap<[usize; 3], usize> = HashMap::new();
+
+    for &c in &grid.active {
+        if let Some(v) = cell_point(grid, surface, c) {
+            let i = mesh.add_vertex(v, normalise(surface.gradient(v)));
+            cell_vertex.insert(c, i);
+        }
+    }
+
+    // Each grid edge is visited from the cell it runs out of the minimum corner of, and the
+    // other three cells around it lie below that cell. If the surface crosses the edge it
+    // passes through all four, so they all have vertices; the grid keeps every cell around a
+    // crossed edge active, octree or not.
+    let axes = [unit_x(), unit_y(), unit_z()];
+    for &g in &grid.active {
+        for axis in 0..3 {
+            let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+
+            // edges on the boundary of the grid don't have four cells around them
+            if g[u] == 0 || g[v] == 0 {
+                continue;
+            }
+
+            let mut h = g;
+            h[axis] += 1;
+            let (d0, d1) = (grid.value(g), grid.value(h));
+            if (d0 < 0.0) == (d1 < 0.0) {
+                continue;
+            }
+
+            let cell = |du: usize, dv: usize| {
+                let mut c = g;
+                c[u] -= du;
+                c[v] -= dv;
+                *cell_vertex
+                    .get(&c)
+                    .expect("every cell around a crossed edge has a vertex")
+            };
+            let q = [cell(1, 1), cell(0, 1), cell(0, 0), cell(1, 0)];
+
+            // the quad faces from the inside end of the edge to the outside
+            let outward = if d0 < 0.0 { axes[axis] } else { -axes[axis] };
+
+            // split along the shorter diagonal
+            let p = q.map(|i| mesh.vertices[i]);
+            if (p[0] - p[2]).length_squared() <= (p[1] - p[3]).length_squared() {
+                mesh.add_triangle_facing(q[0], q[1], q[2], outward);
+                mesh.add_triangle_facing(q[0], q[2], q[3], outward);
+            } else {
+                mesh.add_triangle_facing(q[1], q[2], q[3], outward);
+                mesh.add_triangle_facing(q[1], q[3], q[0], outward);
+            }
+        }
+    }
+
+    mesh
+}
+
+// The vertex for cell c, if the surface passes through it. The QEF minimiser can land outside
+// the cell when the planes are nearly parallel, in which case we fall back to the mass point.
+fn cell_point(grid: &SampleGrid, surface: &dyn ImplicitSurface, c: [usize; 3]) -> Option<Point3> {
+    let mut qef = Qef::new();
+    let mut crossed = false;
+
+    for (a, b) in EDGES {
+        let (ca, cb) = (SampleGrid::corner(c, a), SampleGrid::corner(c, b));
+        let (da, db) = (grid.value(ca), grid.value(cb));
+        if (da < 0.0) == (db < 0.0) {
+            continue;
+        }
+
+        let p = edge_crossing(surface, grid.point(ca), grid.point(cb), da < 0.0);
+        qef.add(p, normalise(surface.gradient(p)));
+        crossed = true;
+    }
+
+    if !crossed {
+        return None;
+    }
+
+    let lo = grid.point(c);
+    let hi = grid.point(SampleGrid::corner(c, 7));
+    let x = qef.solve();
+    let inside = |v: f64, l: f64, h: f64| v >= l && v <= h;
+    if inside(x.x(), lo.x(), hi.x())
+        && inside(x.y(), lo.y(), hi.y())
+        && inside(x.z(), lo.z(), hi.z())
+    {
+        Some(x)
+    } else {
+        Some(qef.mass_point())
+    }
+}
+
+// Bisect the segment a-b for the zero crossing, where a_inside says which side a is on. The
+// samples are only a bound on the distance for CSG and transformed surfaces, so interpolating them
+// would put the crossing in the wrong place.
+fn edge_crossing(surface: &dyn ImplicitSurface, a: Point3, b: Point3, a_inside: bool) -> Point3 {
+    let (mut lo, mut hi) = (a, b);
+    for _ in 0..CROSSING_STEPS {
+        let mid: Vec3 = 0.5 * (lo + hi);
+        if (surface.signed_distance(mid) < 0.0) == a_inside {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    0.5 * (lo + hi)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::csg::Difference;
+    use crate::cuboid::Cuboid;
+    use crate::equation::Equation;
+    use crate::sphere::Sphere;
+    use crate::vec3::{dot, eq, origin};
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_dual_contour_sphere() {
+        let s = Sphere::new(origin(), 1.0);
+        let lo = Vec3::new(-1.5, -1.5, -1.5);
+        let mesh = polygonize(&s, lo, -lo, 16);
+
+        assert!(!mesh.triangles.is_empty());
+        assert!(mesh.is_closed());
+
+        // vertices sit where neighbouring tangent planes meet, just off the curved surface
+        for p in &mesh.vertices {
+            assert!(s.signed_distance(*p).abs() < 0.05);
+        }
+        for t in &mesh.triangles {
+            assert!(dot(mesh.facet_normal(*t), mesh.vertices[t[0]]) > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_dual_contour_keeps_corners() {
+        // a box whose corners fall in the middle of cells, which marching cubes would chamfer
+        let b = Cuboid::new(origin(), Vec3::new(0.73, 0.61, 0.52));
+        let lo = Vec3::new(-1.0, -1.0, -1.0);
+        let mesh = polygonize(&b, lo, -lo, 10);
+        assert!(mesh.is_closed());
+
+        for sx in [-1.0, 1.0] {
+            for sy in [-1.0, 1.0] {
+                for sz in [-1.0, 1.0] {
+                    let corner = Vec3::new(0.73 * sx, 0.61 * sy, 0.52 * sz);
+                    assert!(mesh.vertices.iter().any(|p| eq(*p, corner)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_dual_contour_difference() {
+        // a box with a sphere scooped out of one face
+        let d = Difference::new(
+            Box::new(Cuboid::new(origin(), Vec3::new(0.73, 0.61, 0.52))),
+            Box::new(Sphere::new(0.52 * unit_z(), 0.3)),
+        );
+        let lo = Vec3::new(-1.0, -1.0, -1.0);
+        let mesh = polygonize(&d, lo, -lo, 20);
+        assert!(mesh.is_closed());
+
+        // the far corners are untouched
+        assert!(mesh
+            .vertices
+            .iter()
+            .any(|p| eq(*p, Vec3::new(0.73, 0.61, 0.52))));
+    }
+
+    #[test]
+    fn test_dual_contour_octree() {
+        let b = Cuboid::new(origin(), Vec3::new(0.73, 0.61, 0.52));
+        let lo = Vec3::new(-1.0, -1.0, -1.0);
+
+        let uniform = polygonize(&b, lo, -lo, 16);
+        let adaptive = polygonize_octree(&b, &Octree::new(&b, lo, -lo, 4));
+
+        assert!(adaptive.is_closed());
+        assert_eq!(adaptive.triangles.len(), uniform.triangles.len());
+        assert!(adaptive
+            .vertices
+            .iter()
+            .any(|p| eq(*p, Vec3::new(-0.73, 0.61, -0.52))));
+    }
+
+    // a sphere whose field overestimates the distance twofold, so the octree prunes cells the
+    // surface passes through
+    struct Steep(Sphere);
+
+    impl ImplicitSurface for Steep {
+        fn signed_distance(&self, v: Vec3) -> f64 {
+            2.0 * self.0.signed_distance(v)
+        }
+    }
+
+    #[test]
+    fn test_dual_contour_octree_watertight() {
+        let s = Steep(Sphere::new(Vec3::new(0.1, -0.05, 0.02), 0.9));
+        let lo = Vec3::new(-1.5, -1.5, -1.5);
+
+        let uniform = polygonize(&s, lo, -lo, 32);
+        let adaptive = polygonize_octree(&s, &Octree::new(&s, lo, -lo, 5));
+
+        // every edge is shared by exactly two faces, which the pruned cells would have broken
+        assert!(adaptive.is_closed());
+        assert_eq!(adaptive.triangles.len(), uniform.triangles.len());
+    }
+
+    #[test]
+    fn test_dual_contour_octree_equation() {
+        // a field far from a distance, without a Lipschitz bound, so the tree can't rely on the
+        // value at a cell's centre: at the centre of the box it is -25, well over half the box's
+        // diagonal
+        let e = Equation::parse("100 * (x^2 + y^2 + z^2) - 25", &HashMap::new()).unwrap();
+        let lo = Vec3::new(-2.0, -2.0, -2.0);
+        for depth in 3..=6 {
+            let uniform = polygonize(&e, lo, -lo, 1 << depth);
+            let adaptive = polygonize_octree(&e, &Octree::new(&e, lo, -lo, depth));
+            assert!(!adaptive.triangles.is_empty());
+            assert_eq!(adaptive.triangles.len(), uniform.triangles.len());
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use crate::dual::{self, Dual};
+use crate::hittable::ImplicitSurface;
+use crate::vec3::{self, normalise, Point3, Vec3};
+
+// An axis-aligned ellipsoid with the given semi-axis lengths
+#[derive(Copy, Clone, Default)]
+pub struct Ellipsoid {
+    center: Point3,
+    radii: Vec3,
+}
+
+impl Ellipsoid {
+    pub fn new(center: Point3, radii: Vec3) -> Ellipsoid {
+        Ellipsoid { center, radii }
+    }
+
+    // Returns the closest point on the ellipsoid to v, relative to the centre.
+    //
+    // Unlike the other primitives there is no closed form for this, so we use the method from
+    // Eberly's "Distance from a Point to an Ellipse, an Ellipsoid, or a Hyperellipsoid": fold v
+    // into the positive octant, order the axes from longest to shortest, and bisect for the root of
+    // a function that is monotonic over the bracket containing the closest point.
+    fn closest_point(&self, v: Vec3) -> Vec3 {
+        let p = v - self.center;
+        let e = [self.radii.x(), self.radii.y(), self.radii.z()];
+        let y = [p.x().abs(), p.y().abs(), p.z().abs()];
+
+        let mut order = [0, 1, 2];
+        order.sort_by(|&i, &j| e[j].total_cmp(&e[i]));
+
+        let x = closest_point_3d(
+            [e[order[0]], e[order[1]], e[order[2]]],
+            [y[order[0]], y[order[1]], y[order[2]]],
+        );
+
+        let mut out = [0.0; 3];
+        for (k, &i) in order.iter().enumerate() {
+            out[i] = x[k];
+        }
+
+        vec3::sign(p) * Vec3::new(out[0], out[1], out[2])
+    }
+}
+
+impl ImplicitSurface for Ellipsoid {
+    // signed distance is the distance to the closest point on the surface, negative if v satisfies
+    // the ellipsoid's implicit equation (x/a)^2 + (y/b)^2 + (z/c)^2 < 1
+    fn signed_distance(&self, v: Vec3) -> f64 {
+        let p = v - self.center;
+        let d = (p - self.closest_point(v)).length();
+
+        let r = self.radii;
+        let k = Vec3::new(p.x() / r.x(), p.y() / r.y(), p.z() / r.z());
+        if k.length_squared() < 1.0 {
+            -d
+        } else {
+            d
+        }
+    }
+
+    // the gradient of the distance is the surface normal at the closest point, which is also
+    // well defined when v is on the surface
+    fn gradient(&self, v: Vec3) -> Vec3 {
+        let x = self.closest_point(v);
+        let r = self.radii;
+        normalise(Vec3::new(
+            x.x() / (r.x() * r.x()),
+            x.y() / (r.y() * r.y()),
+            x.z() / (r.z() * r.z()),
+        ))
+    }
+
+    fn signed_distance_dual(&self, v: [Dual; 3]) -> Dual {
+        let p = dual::value(v);
+        dual::chain(v, self.signed_distance(p), self.gradient(p))
+    }
+}
+
+// Bisect for the root of sum_i (n_i / (s + r_i))^2 - 1 where n_i = r_i z_i. The final r must be 1,
+// corresponding to the shortest axis, and g is the function's value at s = 0.
+fn get_root(r: &[f64], z: &[f64], g: f64) -> f64 {
+    let n: Vec<f64> = r.iter().zip(z).map(|(r, z)| r * z).collect();
+
+    let mut s0 = z[z.len() - 1] - 1.0;
+    let mut s1 = if g < 0.0 {
+        0.0
+    } else {
+        f64::sqrt(n.iter().map(|n| n * n).sum()) - 1.0
+    };
+
+    // enough iterations to exhaust the precision of an f64
+    let mut s = s0;
+    for _ in 0..1100 {
+        s = 0.5 * (s0 + s1);
+        if s == s0 || s == s1 {
+            break;
+        }
+
+        let g: f64 = n
+            .iter()
+            .zip(r)
+            .map(|(n, r)| (n / (s + r)).powi(2))
+            .sum::<f64>()
+            - 1.0;
+
+        if g > 0.0 {
+            s0 = s;
+        } else if g < 0.0 {
+            s1 = s;
+        } else {
+            break;
+        }
+    }
+    s
+}
+
+// closest point on an ellipse with e0 >= e1 to y in the positive quadrant
+fn closest_point_2d(e: [f64; 2], y: [f64; 2]) -> [f64; 2] {
+    if y[1] > 0.0 {
+        if y[0] > 0.0 {
+            let z = [y[0] / e[0], y[1] / e[1]];
+            let g = z[0] * z[0] + z[1] * z[1] - 1.0;
+            if g == 0.0 {
+                return y;
+            }
+
+            let r0 = (e[0] / e[1]).powi(2);
+            let s = get_root(&[r0, 1.0], &z, g);
+            return [r0 * y[0] / (s + r0), y[1] / (s + 1.0)];
+        }
+        return [0.0, e[1]];
+    }
+
+    let numer0 = e[0] * y[0];
+    let denom0 = e[0] * e[0] - e[1] * e[1];
+    if numer0 < denom0 {
+        let xde0 = numer0 / denom0;
+        return [e[0] * xde0, e[1] * f64::sqrt(1.0 - xde0 * xde0)];
+    }
+    [e[0], 0.0]
+}
+
+// closest point on an ellipsoid with e0 >= e1 >= e2 to y in the positive octant
+fn closest_point_3d(e: [f64; 3], y: [f64; 3]) -> [f64; 3] {
+    if y[2] > 0.0 {
+        if y[1] > 0.0 {
+            if y[0] > 0.0 {
+                let z = [y[0] / e[0], y[1] / e[1], y[2] / e[2]];
+                let g = z[0] * z[0] + z[1] * z[1] + z[2] * z[2] - 1.0;
+                if g == 0.0 {
+                    return y;
+                }
+
+                let r0 = (e[0] / e[2]).powi(2);
+                let r1 = (e[1] / e[2]).powi(2);
+                let s = get_root(&[r0, r1, 1.0], &z, g);
+                return [r0 * y[0] / (s + r0), r1 * y[1] / (s + r1), y[2] / (s + 1.0)];
+            }
+
+            let x = closest_point_2d([e[1], e[2]], [y[1], y[2]]);
+            return [0.0, x[0], x[1]];
+        }
+
+        if y[0] > 0.0 {
+            let x = closest_point_2d([e[0], e[2]], [y[0], y[2]]);
+            return [x[0], 0.0, x[1]];
+        }
+        return [0.0, 0.0, e[2]];
+    }
+
+    // in the plane of the two longest axes the closest point may still lie off the plane
+    let denom0 = e[0] * e[0] - e[2] * e[2];
+    let denom1 = e[1] * e[1] - e[2] * e[2];
+    let numer0 = e[0] * y[0];
+    let numer1 = e[1] * y[1];
+    if numer0 < denom0 && numer1 < denom1 {
+        let xde0 = numer0 / denom0;
+        let xde1 = numer1 / denom1;
+        let discr = 1.0 - xde0 * xde0 - xde1 * xde1;
+        if discr > 0.0 {
+            return [e[0] * xde0, e[1] * xde1, e[2] * f64::sqrt(discr)];
+        }
+    }
+
+    let x = closest_point_2d([e[0], e[1]], [y[0], y[1]]);
+    [x[0], x[1], 0.0]
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use vec3::{eq, origin, unit_x, unit_y, unit_z};
+
+    use crate::sphere::Sphere;
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_ellipsoid_sdf_coi() {
+        let s = Ellipsoid::new(origin(), Vec3::new(1.0, 3.0, 2.0));
+        assert_relative_eq!(s.signed_distance(unit_x()), 0.0);
+        assert_relative_eq!(s.signed_distance(-3.0 * unit_y()), 0.0);
+        assert_relative_eq!(s.signed_distance(2.0 * unit_z()), 0.0);
+    }
+
+    #[test]
+    fn test_ellipsoid_sdf_outside_inside() {
+        let s = Ellipsoid::new(origin(), Vec3::new(1.0, 3.0, 2.0));
+        assert_relative_eq!(s.signed_distance(4.0 * unit_y()), 1.0);
+        assert_relative_eq!(s.signed_distance(-5.0 * unit_z()), 3.0);
+
+        // from the centre the closest point is at the end of the shortest axis
+        assert_relative_eq!(s.signed_distance(origin()), -1.0);
+        assert_relative_eq!(s.signed_distance(0.5 * unit_x()), -0.5);
+    }
+
+    #[test]
+    fn test_ellipsoid_matches_sphere() {
+        let e = Ellipsoid::new(unit_x(), Vec3::new(2.0, 2.0, 2.0));
+        let s = Sphere::new(unit_x(), 2.0);
+
+        for v in [
+            Vec3::new(3.0, 4.0, 5.0),
+            Vec3::new(-0.5, 0.25, 1.0),
+            Vec3::new(1.0, -7.0, 0.0),
+        ] {
+            assert_relative_eq!(e.signed_distance(v), s.signed_distance(v), epsilon = 1e-9);
+            assert!(eq(e.gradient(v), normalise(s.gradient(v))));
+        }
+    }
+
+    #[test]
+    fn test_ellipsoid_gradient() {
+        let s = Ellipsoid::new(origin(), Vec3::new(1.0, 3.0, 2.0));
+        assert!(eq(s.gradient(-4.0 * unit_y()), -unit_y()));
+        assert!(eq(s.gradient(0.5 * unit_x()), unit_x()));
+
+        // off-axis the gradient is the unit normal at the closest point
+        let v = Vec3::new(1.0, 1.0, 1.0);
+        let g = s.gradient(v);
+        assert_relative_eq!(g.length(), 1.0);
+        let step = 1e-6;
+        let d0 = s.signed_distance(v);
+        assert_relative_eq!(
+            (s.signed_distance(v + step * unit_x()) - d0) / step,
+            g.x(),
+            epsilon = 1e-4
+        );
+    }
+}
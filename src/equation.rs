@@ -0,0 +1,728 @@
+use std::collections::HashMap;
+use std::f64::consts::{E, PI, TAU};
+use std::fmt;
+
+use crate::dual::{Dual, Scalar};
+use crate::hittable::ImplicitSurface;
+use crate::interval::Interval;
+use crate::polynomial::{self, Polynomial3};
+use crate::vec3::Vec3;
+
+// A surface given by an equation F(x, y, z) typed into the config, e.g.
+//
+//   x^2 + y^2 + z^2 - r^2
+//
+// with the parameter r supplied alongside it. The surface is where F is zero, with F negative
+// inside. The grammar is the usual one:
+//
+//   sum     = product (("+" | "-") product)*
+//   product = unary (("*" | "/") unary)*
+//   unary   = "-" unary | power
+//   power   = atom ("^" unary)?
+//   atom    = number | name | name "(" sum ("," sum)* ")" | "(" sum ")"
+//
+// so ^ binds tighter than unary minus and is right associative. Names are the variables x, y and
+// z, the constants pi, tau and e, and any parameters; parameters are substituted when parsing so
+// they cost nothing to evaluate.
+//
+// The gradient is found by differentiating the parsed expression symbolically, once, and
+// evaluating the three partial derivatives alongside F. F can also be evaluated on dual numbers,
+// for surfaces built from it.
+//
+// F is used directly as the distance, so it should be scaled to be close to one, or sphere
+// tracing may step straight through the surface.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    // 1-based position in the equation of the character where the problem is
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Func {
+    Sqrt,
+    Abs,
+    Sign,
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Exp,
+    Ln,
+}
+
+impl Func {
+    fn apply<T: Scalar>(self, x: T) -> T {
+        match self {
+            Func::Sqrt => x.sqrt(),
+            Func::Abs => x.abs(),
+            Func::Sign => x.sign(),
+            Func::Sin => x.sin(),
+            Func::Cos => x.cos(),
+            Func::Tan => x.tan(),
+            Func::Asin => x.asin(),
+            Func::Acos => x.acos(),
+            Func::Atan => x.atan(),
+            Func::Exp => x.exp(),
+            Func::Ln => x.ln(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Const(f64),
+    // x, y or z
+    Var(usize),
+    Neg(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+    Pow(Box<Expr>, Box<Expr>),
+    Call(Func, Box<Expr>),
+    // if a < b then c else d, which min and max become so that they can be differentiated
+    Select(Box<[Expr; 4]>),
+}
+
+// Constructors that fold constants and drop zeros and ones, so derivatives don't fill up with
+// terms like 0 * x + 1 * 1.
+
+fn neg(a: Expr) -> Expr {
+    match a {
+        Expr::Const(a) => Expr::Const(-a),
+        Expr::Neg(a) => *a,
+        a => Expr::Neg(Box::new(a)),
+    }
+}
+
+fn add(a: Expr, b: Expr) -> Expr {
+    match (a, b) {
+        (Expr::Const(a), Expr::Const(b)) => Expr::Const(a + b),
+        (Expr::Const(0.0), e) | (e, Expr::Const(0.0)) => e,
+        (a, b) => Expr::Add(Box::new(a), Box::new(b)),
+    }
+}
+
+fn sub(a: Expr, b: Expr) -> Expr {
+    match (a, b) {
+        (Expr::Const(a), Expr::Const(b)) => Expr::Const(a - b),
+        (a, Expr::Const(0.0)) => a,
+        (Expr::Const(0.0), b) => neg(b),
+        (a, b) => Expr::Sub(Box::new(a), Box::new(b)),
+    }
+}
+
+fn mul(a: Expr, b: Expr) -> Expr {
+    match (a, b) {
+        (Expr::Const(a), Expr::Const(b)) => Expr::Const(a * b),
+        (Expr::Const(0.0), _) | (_, Expr::Const(0.0)) => Expr::Const(0.0),
+        (Expr::Const(1.0), e) | (e, Expr::Const(1.0)) => e,
+        (a, b) => Expr::Mul(Box::new(a), Box::new(b)),
+    }
+}
+
+fn div(a: Expr, b: Expr) -> Expr {
+    match (a, b) {
+        (Expr::Const(a), Expr::Const(b)) => Expr::Const(a / b),
+        (Expr::Const(0.0), _) => Expr::Const(0.0),
+        (a, Expr::Const(1.0)) => a,
+        (a, b) => Expr::Div(Box::new(a), Box::new(b)),
+    }
+}
+
+fn pow(a: Expr, b: Expr) -> Expr {
+    match (a, b) {
+        (Expr::Const(a), Expr::Const(b)) => Expr::Const(a.powf(b)),
+        (_, Expr::Const(0.0)) => Expr::Const(1.0),
+        (a, Expr::Const(1.0)) => a,
+        (a, b) => Expr::Pow(Box::new(a), Box::new(b)),
+    }
+}
+
+fn call(f: Func, a: Expr) -> Expr {
+    match a {
+        Expr::Const(a) => Expr::Const(f.apply(a)),
+        a => Expr::Call(f, Box::new(a)),
+    }
+}
+
+fn select(a: Expr, b: Expr, c: Expr, d: Expr) -> Expr {
+    match (&a, &b) {
+        (Expr::Const(x), Expr::Const(y)) => {
+            if x < y {
+                c
+            } else {
+                d
+            }
+        }
+        _ if c == d => c,
+        _ => Expr::Select(Box::new([a, b, c, d])),
+    }
+}
+
+impl Expr {
+    // the expression as a polynomial in x, y and z, if it is one of at most polynomial::MAX_DEGREE
+    fn polynomial(&self) -> Option<Polynomial3> {
+        let p = match self {
+            Expr::Const(c) => Polynomial3::constant(*c),
+            Expr::Var(i) => Polynomial3::variable(*i),
+            Expr::Neg(a) => -a.polynomial()?,
+            Expr::Add(a, b) => a.polynomial()? + b.polynomial()?,
+            Expr::Sub(a, b) => a.polynomial()? - b.polynomial()?,
+            Expr::Mul(a, b) => a.polynomial()? * b.polynomial()?,
+            Expr::Div(a, b) => match **b {
+                Expr::Const(c) => a.polynomial()? * (1.0 / c),
+                _ => return None,
+            },
+            // checked before expanding, which for a large power would take forever
+            Expr::Pow(a, b) => match **b {
+                Expr::Const(n) if n >= 0.0 && n == n.round() => {
+                    let a = a.polynomial()?;
+                    if n * a.degree().max(1) as f64 > polynomial::MAX_DEGREE as f64 {
+                        return None;
+                    }
+                    a.powi(n as u32)
+                }
+                _ => return None,
+            },
+            Expr::Call(..) | Expr::Select(..) => return None,
+        };
+        (p.degree() <= polynomial::MAX_DEGREE).then_some(p)
+    }
+
+    fn eval<T: Scalar>(&self, v: [T; 3]) -> T {
+        match self {
+            Expr::Const(c) => T::constant(*c),
+            Expr::Var(i) => v[*i],
+            Expr::Neg(a) => -a.eval(v),
+            Expr::Add(a, b) => a.eval(v) + b.eval(v),
+            Expr::Sub(a, b) => a.eval(v) - b.eval(v),
+            Expr::Mul(a, b) => a.eval(v) * b.eval(v),
+            Expr::Div(a, b) => a.eval(v) / b.eval(v),
+            // a constant exponent, unlike the general case, works for negative a
+            Expr::Pow(a, b) => match **b {
+                Expr::Const(c) => a.eval(v).powf(c),
+                _ => a.eval(v).pow(b.eval(v)),
+            },
+            Expr::Call(f, a) => f.apply(a.eval(v)),
+            Expr::Select(s) => s[0]
+                .eval(v)
+                .select(s[1].eval(v), s[2].eval(v), s[3].eval(v)),
+        }
+    }
+
+    // partial derivative with respect to variable i
+    fn derivative(&self, i: usize) -> Expr {
+        let d = |e: &Expr| e.derivative(i);
+        match self {
+            Expr::Const(_) => Expr::Const(0.0),
+            Expr::Var(j) => Expr::Const(if *j == i { 1.0 } else { 0.0 }),
+            Expr::Neg(a) => neg(d(a)),
+            Expr::Add(a, b) => add(d(a), d(b)),
+            Expr::Sub(a, b) => sub(d(a), d(b)),
+            Expr::Mul(a, b) => add(mul(d(a), *b.clone()), mul(*a.clone(), d(b))),
+            Expr::Div(a, b) => div(
+                sub(mul(d(a), *b.clone()), mul(*a.clone(), d(b))),
+                pow(*b.clone(), Expr::Const(2.0)),
+            ),
+            Expr::Pow(a, b) => match **b {
+                // the common case, which unlike the general one works for negative a
+                Expr::Const(c) => mul(
+                    mul(Expr::Const(c), pow(*a.clone(), Expr::Const(c - 1.0))),
+                    d(a),
+                ),
+                _ => mul(
+                    self.clone(),
+                    add(
+                        mul(d(b), call(Func::Ln, *a.clone())),
+                        div(mul(*b.clone(), d(a)), *a.clone()),
+                    ),
+                ),
+            },
+            Expr::Call(f, a) => {
+                let a = *a.clone();
+                let outer = match f {
+                    Func::Sqrt => div(Expr::Const(0.5), call(Func::Sqrt, a.clone())),
+                    Func::Abs => call(Func::Sign, a.clone()),
+                    Func::Sign => Expr::Const(0.0),
+                    Func::Sin => call(Func::Cos, a.clone()),
+                    Func::Cos => neg(call(Func::Sin, a.clone())),
+                    Func::Tan => div(
+                        Expr::Const(1.0),
+                        pow(call(Func::Cos, a.clone()), Expr::Const(2.0)),
+                    ),
+                    Func::Asin | Func::Acos => {
+                        let s = div(
+                            Expr::Const(1.0),
+                            call(
+                                Func::Sqrt,
+                                sub(Expr::Const(1.0), pow(a.clone(), Expr::Const(2.0))),
+                            ),
+                        );
+                        if *f == Func::Asin {
+                            s
+                        } else {
+                            neg(s)
+                        }
+                    }
+                    Func::Atan => div(
+                        Expr::Const(1.0),
+                        add(Expr::Const(1.0), pow(a.clone(), Expr::Const(2.0))),
+                    ),
+                    Func::Exp => call(Func::Exp, a.clone()),
+                    Func::Ln => div(Expr::Const(1.0), a.clone()),
+                };
+                mul(outer, d(&a))
+            }
+            Expr::Select(s) => select(s[0].clone(), s[1].clone(), d(&s[2]), d(&s[3])),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Symbol(char),
+    End,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Number(x) => write!(f, "`{}`", x),
+            Token::Name(n) => write!(f, "`{}`", n),
+            Token::Symbol(c) => write!(f, "`{}`", c),
+            Token::End => write!(f, "end of equation"),
+        }
+    }
+}
+
+fn error<T>(column: usize, message: String) -> Result<T, ParseError> {
+    Err(ParseError { column, message })
+}
+
+// split the source into tokens, each with its column
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        if c.is_ascii_digit() || c == '.' {
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            // an exponent, as long as it's followed by digits so 2e means 2 * e
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let mut j = i + 1;
+                if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                    j += 1;
+                }
+                if j < chars.len() && chars[j].is_ascii_digit() {
+                    i = j;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+
+            let text: String = chars[start..i].iter().collect();
+            match text.parse() {
+                Ok(x) => tokens.push((Token::Number(x), start + 1)),
+                Err(_) => return error(start + 1, format!("invalid number `{}`", text)),
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push((Token::Name(chars[start..i].iter().collect()), start + 1));
+        } else if "+-*/^(),".contains(c) {
+            tokens.push((Token::Symbol(c), start + 1));
+            i += 1;
+        } else {
+            return error(start + 1, format!("unexpected character `{}`", c));
+        }
+    }
+
+    tokens.push((Token::End, chars.len() + 1));
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    parameters: &'a HashMap<String, f64>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn column(&self) -> usize {
+        self.tokens[self.pos].1
+    }
+
+    fn next(&mut self) -> (Token, usize) {
+        let t = self.tokens[self.pos].clone();
+        if t.0 != Token::End {
+            self.pos += 1;
+        }
+        t
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if *self.peek() == Token::Symbol(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), ParseError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            error(
+                self.column(),
+                format!("expected `{}` but found {}", c, self.peek()),
+            )
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, ParseError> {
+        let mut e = self.product()?;
+        loop {
+            if self.eat('+') {
+                e = add(e, self.product()?);
+            } else if self.eat('-') {
+                e = sub(e, self.product()?);
+            } else {
+                return Ok(e);
+            }
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, ParseError> {
+        let mut e = self.unary()?;
+        loop {
+            if self.eat('*') {
+                e = mul(e, self.unary()?);
+            } else if self.eat('/') {
+                e = div(e, self.unary()?);
+            } else {
+                return Ok(e);
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        if self.eat('-') {
+            Ok(neg(self.unary()?))
+        } else {
+            self.power()
+        }
+    }
+
+    fn power(&mut self) -> Result<Expr, ParseError> {
+        let base = self.atom()?;
+        if self.eat('^') {
+            Ok(pow(base, self.unary()?))
+        } else {
+            Ok(base)
+        }
+    }
+
+    fn atom(&mut self) -> Result<Expr, ParseError> {
+        match self.next() {
+            (Token::Number(x), _) => Ok(Expr::Const(x)),
+            (Token::Symbol('('), _) => {
+                let e = self.sum()?;
+                self.expect(')')?;
+                Ok(e)
+            }
+            (Token::Name(name), column) => {
+                if *self.peek() == Token::Symbol('(') {
+                    self.call(&name, column)
+                } else {
+                    self.name(&name, column)
+                }
+            }
+            (t, column) => error(column, format!("expected a value but found {}", t)),
+        }
+    }
+
+    fn name(&self, name: &str, column: usize) -> Result<Expr, ParseError> {
+        if let Some(i) = ["x", "y", "z"].iter().position(|v| *v == name) {
+            return Ok(Expr::Var(i));
+        }
+        if let Some(p) = self.parameters.get(name) {
+            return Ok(Expr::Const(*p));
+        }
+        match name {
+            "pi" => Ok(Expr::Const(PI)),
+            "tau" => Ok(Expr::Const(TAU)),
+            "e" => Ok(Expr::Const(E)),
+            _ => error(column, format!("unknown name `{}`", name)),
+        }
+    }
+
+    fn call(&mut self, name: &str, column: usize) -> Result<Expr, ParseError> {
+        self.expect('(')?;
+        let mut args = vec![self.sum()?];
+        while self.eat(',') {
+            args.push(self.sum()?);
+        }
+        self.expect(')')?;
+
+        let arity = |n: usize| {
+            if args.len() == n {
+                Ok(())
+            } else {
+                error(
+                    column,
+                    format!("`{}` takes {} argument(s) but got {}", name, n, args.len()),
+                )
+            }
+        };
+
+        let f = match name {
+            "sqrt" => Func::Sqrt,
+            "abs" => Func::Abs,
+            "sign" => Func::Sign,
+            "sin" => Func::Sin,
+            "cos" => Func::Cos,
+            "tan" => Func::Tan,
+            "asin" => Func::Asin,
+            "acos" => Func::Acos,
+            "atan" => Func::Atan,
+            "exp" => Func::Exp,
+            "ln" => Func::Ln,
+            "pow" => {
+                arity(2)?;
+                let b = args.pop().expect("two arguments");
+                return Ok(pow(args.pop().expect("two arguments"), b));
+            }
+            "min" | "max" => {
+                if args.len() < 2 {
+                    return error(
+                        column,
+                        format!("`{}` takes at least 2 arguments but got 1", name),
+                    );
+                }
+                let is_min = name == "min";
+                let mut it = args.into_iter();
+                let first = it.next().expect("at least two arguments");
+                return Ok(it.fold(first, |a, b| {
+                    if is_min {
+                        select(a.clone(), b.clone(), a, b)
+                    } else {
+                        select(a.clone(), b.clone(), b, a)
+                    }
+                }));
+            }
+            _ => return error(column, format!("unknown function `{}`", name)),
+        };
+
+        arity(1)?;
+        Ok(call(f, args.pop().expect("one argument")))
+    }
+}
+
+// The surface F(x, y, z) = 0. F is not usually a distance, so for ray tracing it can be given a
+// bound on the length of its gradient over the region being rendered, see
+// ImplicitSurface::lipschitz.
+pub struct Equation {
+    f: Expr,
+    gradient: [Expr; 3],
+    lipschitz: f64,
+}
+
+impl Equation {
+    pub fn parse(source: &str, parameters: &HashMap<String, f64>) -> Result<Equation, ParseError> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            pos: 0,
+            parameters,
+        };
+
+        let f = parser.sum()?;
+        if *parser.peek() != Token::End {
+            return error(
+                parser.column(),
+                format!("unexpected {} after the end of the equation", parser.peek()),
+            );
+        }
+
+        let gradient = [f.derivative(0), f.derivative(1), f.derivative(2)];
+        Ok(Equation {
+            f,
+            gradient,
+            lipschitz: 1.0,
+        })
+    }
+
+    pub fn with_lipschitz(self, lipschitz: f64) -> Equation {
+        Equation { lipschitz, ..self }
+    }
+
+    // F as a polynomial, if it only adds, subtracts and multiplies x, y, z and numbers, divides by
+    // numbers and raises to whole powers, up to polynomial::MAX_DEGREE
+    pub fn polynomial(&self) -> Option<Polynomial3> {
+        self.f.polynomial()
+    }
+}
+
+impl ImplicitSurface for Equation {
+    fn signed_distance(&self, v: Vec3) -> f64 {
+        self.f.eval([v.x(), v.y(), v.z()])
+    }
+
+    fn signed_distance_dual(&self, v: [Dual; 3]) -> Dual {
+        self.f.eval(v)
+    }
+
+    fn gradient(&self, v: Vec3) -> Vec3 {
+        let v = [v.x(), v.y(), v.z()];
+        let [dx, dy, dz] = &self.gradient;
+        Vec3::new(dx.eval(v), dy.eval(v), dz.eval(v))
+    }
+
+    fn lipschitz(&self, _v: Vec3, _radius: f64) -> f64 {
+        self.lipschitz
+    }
+
+    fn signed_distance_interval(&self, v: [Interval; 3]) -> Interval {
+        self.f.eval(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::vec3::eq;
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    fn parse(source: &str) -> Result<Equation, ParseError> {
+        Equation::parse(source, &HashMap::new())
+    }
+
+    fn value(source: &str) -> f64 {
+        parse(source)
+            .unwrap()
+            .signed_distance(Vec3::new(1.0, 2.0, 3.0))
+    }
+
+    #[test]
+    fn test_equation_precedence() {
+        assert_relative_eq!(value("1 + 2 * 3"), 7.0);
+        assert_relative_eq!(value("(1 + 2) * 3"), 9.0);
+        assert_relative_eq!(value("-2^2"), -4.0);
+        assert_relative_eq!(value("2^3^2"), 512.0);
+        assert_relative_eq!(value("2^-1"), 0.5);
+        assert_relative_eq!(value("8 / 4 / 2"), 1.0);
+        assert_relative_eq!(value("1.5e1 - 2 * e"), 15.0 - 2.0 * E);
+        assert_relative_eq!(value("x + y * z"), 7.0);
+        assert_relative_eq!(value("max(x, y, z) - min(x, -y)"), 5.0);
+        assert_relative_eq!(value("pow(z, 2) + abs(-x) + sqrt(4)"), 12.0);
+        assert_relative_eq!(value("cos(pi)"), -1.0);
+    }
+
+    #[test]
+    fn test_equation_sphere() {
+        let params = HashMap::from([("r".to_string(), 2.0)]);
+        let s = Equation::parse("sqrt(x^2 + y^2 + z^2) - r", &params).unwrap();
+
+        let v = Vec3::new(3.0, 0.0, 4.0);
+        assert_relative_eq!(s.signed_distance(v), 3.0);
+        assert!(eq(s.gradient(v), Vec3::new(0.6, 0.0, 0.8)));
+    }
+
+    #[test]
+    fn test_equation_gradient() {
+        // compare the symbolic gradient against central differences
+        let s = parse("sin(x * y) + exp(z) / (1 + x^2) - atan(y)^z + max(x, z)").unwrap();
+        let v = Vec3::new(0.3, 0.7, 1.1);
+        let h = 1e-6;
+        let axes = [
+            Vec3::new(h, 0.0, 0.0),
+            Vec3::new(0.0, h, 0.0),
+            Vec3::new(0.0, 0.0, h),
+        ];
+        let g = s.gradient(v);
+        let g = [g.x(), g.y(), g.z()];
+
+        for (a, gi) in axes.iter().zip(g) {
+            let fd = (s.signed_distance(v + *a) - s.signed_distance(v - *a)) / (2.0 * h);
+            assert_relative_eq!(gi, fd, epsilon = 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_equation_polynomial() {
+        let p = parse("(x - 1)^2 * y / 2 + z^3 - 4")
+            .unwrap()
+            .polynomial()
+            .unwrap();
+        assert_eq!(p.degree(), 3);
+        assert_relative_eq!(p.eval(Vec3::new(3.0, 2.0, 1.0)), 1.0);
+
+        assert!(parse("sqrt(x)").unwrap().polynomial().is_none());
+        assert!(parse("x / y").unwrap().polynomial().is_none());
+        assert!(parse("x^0.5").unwrap().polynomial().is_none());
+        assert!(parse("min(x, y)").unwrap().polynomial().is_none());
+
+        // too high a degree to trace, however it's reached, without expanding it first
+        assert!(parse("x^16").unwrap().polynomial().is_some());
+        assert!(parse("x^100000000").unwrap().polynomial().is_none());
+        assert!(parse("(x*y)^9").unwrap().polynomial().is_none());
+        assert!(parse("x^9 * y^8").unwrap().polynomial().is_none());
+    }
+
+    #[test]
+    fn test_equation_errors() {
+        let err = |source: &str| parse(source).err().unwrap();
+
+        assert_eq!(
+            err("x + sine(y)"),
+            ParseError {
+                column: 5,
+                message: "unknown function `sine`".to_string()
+            }
+        );
+        assert_eq!(err("x + r").column, 5);
+        assert_eq!(err("x * (y + 1").column, 11);
+        assert_eq!(err("x $ y").column, 3);
+        assert_eq!(err("x y").column, 3);
+        assert_eq!(err("2 * ").column, 5);
+        assert_eq!(
+            err("pow(x)").to_string(),
+            "column 1: `pow` takes 2 argument(s) but got 1"
+        );
+    }
+}
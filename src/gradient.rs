@@ -0,0 +1,138 @@
+use crate::hittable::ImplicitSurface;
+use crate::vec3::{cross, dot, unit_x, unit_y, unit_z, Vec3};
+
+// Numeric gradients by finite differences, for fields with neither a dual distance nor a
+// hand-written gradient, e.g. ones displaced by noise. Epsilon is the step, in world units: too
+// large and detail is smoothed over, too small and rounding error takes over.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Stencil {
+    // a pair of samples either side of v along each axis, so six evaluations
+    Central { epsilon: f64 },
+    // the four corners of a tetrahedron around v, so two thirds of the evaluations, but the
+    // error only falls off linearly with epsilon rather than quadratically
+    Tetrahedral { epsilon: f64 },
+}
+
+impl Default for Stencil {
+    fn default() -> Self {
+        Stencil::Central { epsilon: 1e-6 }
+    }
+}
+
+pub fn numeric<S: ImplicitSurface + ?Sized>(s: &S, v: Vec3, stencil: Stencil) -> Vec3 {
+    match stencil {
+        Stencil::Central { epsilon: h } => {
+            let diff = |a: Vec3| s.signed_distance(v + h * a) - s.signed_distance(v - h * a);
+            Vec3::new(diff(unit_x()), diff(unit_y()), diff(unit_z())) / (2.0 * h)
+        }
+        Stencil::Tetrahedral { epsilon: h } => {
+            // summing each corner's sample times its direction leaves 4 h grad f
+            let corners = [
+                Vec3::new(1.0, -1.0, -1.0),
+                Vec3::new(-1.0, -1.0, 1.0),
+                Vec3::new(-1.0, 1.0, -1.0),
+                Vec3::new(1.0, 1.0, 1.0),
+            ];
+            let mut g = Vec3::default();
+            for k in corners {
+                g += s.signed_distance(v + h * k) * k;
+            }
+            g / (4.0 * h)
+        }
+    }
+}
+
+// Debugging aid: the largest angle, in radians, between the surface's own gradient and the
+// numeric one over the given points. Points where either gradient vanishes are skipped since
+// they have no direction to compare.
+pub fn max_angular_error(s: &dyn ImplicitSurface, points: &[Vec3], stencil: Stencil) -> f64 {
+    points
+        .iter()
+        .map(|&v| (s.gradient(v), numeric(s, v, stencil)))
+        .filter(|(a, n)| a.length_squared() > 0.0 && n.length_squared() > 0.0)
+        .map(|(a, n)| f64::atan2(cross(a, n).length(), dot(a, n)))
+        .fold(0.0, f64::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cylinder::Cylinder;
+    use crate::sphere::Sphere;
+    use crate::vec3::{eq, normalise, origin};
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    // a sphere with nothing but its distance
+    struct Bare {
+        stencil: Stencil,
+    }
+
+    impl ImplicitSurface for Bare {
+        fn signed_distance(&self, v: Vec3) -> f64 {
+            v.length() - 1.0
+        }
+
+        fn stencil(&self) -> Stencil {
+            self.stencil
+        }
+    }
+
+    #[test]
+    fn test_numeric_stencils() {
+        let s = Sphere::new(origin(), 1.0);
+        let v = Vec3::new(0.3, -1.2, 0.8);
+
+        for stencil in [
+            Stencil::Central { epsilon: 1e-5 },
+            Stencil::Tetrahedral { epsilon: 1e-5 },
+        ] {
+            assert!(eq(numeric(&s, v, stencil), normalise(v)));
+        }
+    }
+
+    #[test]
+    fn test_default_gradient_is_numeric() {
+        let v = Vec3::new(2.0, 1.0, -2.0);
+        for stencil in [Stencil::default(), Stencil::Tetrahedral { epsilon: 1e-4 }] {
+            let s = Bare { stencil };
+            assert!(eq(s.gradient(v), v / 3.0));
+        }
+    }
+
+    #[test]
+    fn test_max_angular_error() {
+        let points: Vec<Vec3> = (0..50)
+            .map(|i| {
+                let t = i as f64;
+                Vec3::new(2.0 * t.sin(), 1.5 * (0.7 * t).cos(), t / 25.0 - 1.0)
+            })
+            .collect();
+
+        let sphere = Sphere::new(origin(), 1.0);
+        let cylinder = Cylinder::new(origin(), unit_y(), 0.5);
+        for s in [&sphere as &dyn ImplicitSurface, &cylinder] {
+            assert!(max_angular_error(s, &points, Stencil::default()) < 1e-6);
+            // first order, so it drifts further off with a larger step
+            let coarse = Stencil::Tetrahedral { epsilon: 1e-3 };
+            assert!(max_angular_error(s, &points, coarse) < 1e-2);
+        }
+
+        // a gradient pointing the wrong way is as far out as it gets
+        let flipped = Bare {
+            stencil: Stencil::default(),
+        };
+        struct Inverted(Bare);
+        impl ImplicitSurface for Inverted {
+            fn signed_distance(&self, v: Vec3) -> f64 {
+                self.0.signed_distance(v)
+            }
+
+            fn gradient(&self, v: Vec3) -> Vec3 {
+                -self.0.gradient(v)
+            }
+        }
+        let e = max_angular_error(&Inverted(flipped), &points, Stencil::default());
+        assert!((e - std::f64::consts::PI).abs() < 1e-6);
+    }
+}
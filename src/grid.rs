@@ -0,0 +1,159 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::hittable::ImplicitSurface;
+use crate::octree::Octree;
+use crate::vec3::{Point3, Vec3};
+
+// Corner i of a cell is offset from its minimum corner by the bits of i: x is bit 0, y bit 1 and
+// z bit 2. Edges join corners that differ in exactly one bit.
+pub(crate) const EDGES: [(usize, usize); 12] = [
+    (0, 1),
+    (2, 3),
+    (4, 5),
+    (6, 7),
+    (0, 2),
+    (1, 3),
+    (4, 6),
+    (5, 7),
+    (0, 4),
+    (1, 5),
+    (2, 6),
+    (3, 7),
+];
+
+// The signed distance sampled at the corners of a grid of cubic cells covering a box. Only the
+// active cells are sampled, which is all of them for a uniform grid but just those near the
+// surface when the grid comes from an octree.
+pub(crate) struct SampleGrid {
+    pub min: Point3,
+    pub step: f64,
+    // number of cells along each axis, there is one more sample than this
+    pub cells: [usize; 3],
+    pub active: Vec<[usize; 3]>,
+    values: HashMap<usize, f64>,
+}
+
+impl SampleGrid {
+    // resolution is the number of cells along the longest side of the box
+    pub fn new(surface: &dyn ImplicitSurface, min: Point3, max: Point3, resolution: usize) -> Self {
+        let size = max - min;
+        let step = size.max_component() / resolution.max(1) as f64;
+        let cells = [size.x(), size.y(), size.z()].map(|s| f64::ceil(s / step).max(1.0) as usize);
+
+        let mut active = Vec::with_capacity(cells[0] * cells[1] * cells[2]);
+        for z in 0..cells[2] {
+            for y in 0..cells[1] {
+                for x in 0..cells[0] {
+                    active.push([x, y, z]);
+                }
+            }
+        }
+
+        SampleGrid::sample(surface, min, step, cells, active)
+    }
+
+    // The finest cells of an octree, all of which are the same size. The tree only goes by the
+    // field at the centres of cells, so it can prune one next to a crossed edge of a cell it kept,
+    // e.g. where the field overestimates the distance. Those are added back, and in turn any
+    // beyond them, so that all four cells around every crossed edge are active and the mesh has
+    // no holes.
+    pub fn from_octree(surface: &dyn ImplicitSurface, tree: &Octree) -> Self {
+        let n = 1 << tree.depth();
+        let mut grid =
+            SampleGrid::sample(surface, tree.min(), tree.cell_size(), [n; 3], tree.leaves());
+        grid.close(surface);
+        grid
+    }
+
+    fn sample(
+        surface: &dyn ImplicitSurface,
+        min: Point3,
+        step: f64,
+        cells: [usize; 3],
+        active: Vec<[usize; 3]>,
+    ) -> Self {
+        let mut grid = SampleGrid {
+            min,
+            step,
+            cells,
+            active: Vec::with_capacity(active.len()),
+            values: HashMap::new(),
+        };
+        for c in active {
+            grid.add(surface, c);
+        }
+        grid
+    }
+
+    // makes cell c active, sampling those of its corners that aren't yet
+    fn add(&mut self, surface: &dyn ImplicitSurface, c: [usize; 3]) {
+        for i in 0..8 {
+            let corner = SampleGrid::corner(c, i);
+            let p = self.point(corner);
+            self.values
+                .entry(self.index(corner))
+                .or_insert_with(|| surface.signed_distance(p));
+        }
+        self.active.push(c);
+    }
+
+    // activates the missing cells around each crossed edge of the active cells, until there are
+    // none
+    fn close(&mut self, surface: &dyn ImplicitSurface) {
+        let mut seen: HashSet<[usize; 3]> = self.active.iter().copied().collect();
+
+        let mut next = 0;
+        while next < self.active.len() {
+            let c = self.active[next];
+            next += 1;
+
+            for (a, b) in EDGES {
+                // a is the lower end of each edge
+                let (ca, cb) = (SampleGrid::corner(c, a), SampleGrid::corner(c, b));
+                if (self.value(ca) < 0.0) == (self.value(cb) < 0.0) {
+                    continue;
+                }
+
+                let axis = (a ^ b).trailing_zeros() as usize;
+                let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+                for (du, dv) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    if ca[u] < du || ca[v] < dv {
+                        continue;
+                    }
+                    let mut n = ca;
+                    n[u] -= du;
+                    n[v] -= dv;
+                    if n[u] < self.cells[u] && n[v] < self.cells[v] && seen.insert(n) {
+                        self.add(surface, n);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn point(&self, c: [usize; 3]) -> Point3 {
+        self.min + self.step * Vec3::new(c[0] as f64, c[1] as f64, c[2] as f64)
+    }
+
+    fn index(&self, c: [usize; 3]) -> usize {
+        (c[2] * (self.cells[1] + 1) + c[1]) * (self.cells[0] + 1) + c[0]
+    }
+
+    // the sample at a corner of one of the active cells
+    pub fn value(&self, c: [usize; 3]) -> f64 {
+        self.values[&self.index(c)]
+    }
+
+    // the grid coordinates of corner i of the cell whose minimum corner is c
+    pub fn corner(c: [usize; 3], i: usize) -> [usize; 3] {
+        [c[0] + (i & 1), c[1] + ((i >> 1) & 1), c[2] + ((i >> 2) & 1)]
+    }
+
+    // a key for the grid edge running from corner a of cell c to corner b, shared by all four
+    // cells around the edge
+    pub fn edge_key(&self, c: [usize; 3], a: usize, b: usize) -> usize {
+        let (lo, hi) = if a < b { (a, b) } else { (b, a) };
+        let axis = (hi ^ lo).trailing_zeros() as usize;
+        3 * self.index(SampleGrid::corner(c, lo)) + axis
+    }
+}
@@ -1,4 +1,10 @@
-use crate::ray::Ray;
+use std::rc::Rc;
+
+use crate::dual::{self, Dual};
+use crate::gradient::{self, Stencil};
+use crate::interval::{self, Interval};
+use crate::material::Material;
+use crate::ray::{MarchSettings, Ray};
 use crate::vec3::{self, Point3, Vec3};
 
 #[derive(Clone, Default)]
@@ -7,6 +13,8 @@ pub struct HitRecord {
     pub normal: Vec3,
     pub t: f64,
     pub front_face: bool,
+    // what the surface hit is made of, given by the list it's in
+    pub material: Option<Rc<dyn Material>>,
 }
 
 impl HitRecord {
@@ -22,15 +30,99 @@ impl HitRecord {
             -outward_normal
         };
     }
+
+    // A ray leaving the hit in direction d, e.g. reflected or refracted. It starts a little off
+    // the surface, along the normal to whichever side d heads, so it doesn't find the surface it
+    // leaves.
+    pub fn spawn(&self, d: Vec3, march: &MarchSettings) -> Ray {
+        let side = if vec3::dot(d, self.normal) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        let offset = march.offset(self.t, self.p);
+        Ray::new(self.p + (side * offset) * self.normal, d)
+    }
 }
 
 pub trait Hittable {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
 }
 
+// Surfaces implement signed_distance and, where they can, signed_distance_dual. Writing the
+// distance once against dual::Scalar and using it for both gives an exact gradient for free;
+// surfaces that work out their gradient some other way can give it to dual::chain instead.
+// Failing that the gradient is found numerically, with the surface's stencil.
 pub trait ImplicitSurface {
     // returns the signed distance from v to the implicit surface
     fn signed_distance(&self, v: Vec3) -> f64;
+
+    // returns the signed distance at a point given as dual numbers, along with its derivatives
+    fn signed_distance_dual(&self, v: [Dual; 3]) -> Dual {
+        let p = dual::value(v);
+        let g = gradient::numeric(self, p, self.stencil());
+        dual::chain(v, self.signed_distance(p), g)
+    }
+
     // returns the gradient of the distance function at v
-    fn gradient(&self, v: Vec3) -> Vec3;
+    fn gradient(&self, v: Vec3) -> Vec3 {
+        self.signed_distance_dual(Dual::variables(v)).grad
+    }
+
+    // how to differentiate signed_distance numerically, when it comes to that
+    fn stencil(&self) -> Stencil {
+        Stencil::default()
+    }
+
+    // A bound on how fast signed_distance can change, i.e. on the length of its gradient, within
+    // radius of v. Distance functions change at exactly one unit per unit. Other functions, like
+    // most algebraic surfaces, must give a bound, for all of space or just around v, so that ray
+    // tracing can step safely. It must be positive.
+    fn lipschitz(&self, _v: Vec3, _radius: f64) -> f64 {
+        1.0
+    }
+
+    // Bounds signed_distance over a box given as intervals. By default this is the distance at
+    // the centre give or take the Lipschitz bound times the radius of the box, which is as good
+    // as it gets for distance functions. Surfaces that aren't distances should evaluate
+    // themselves on the intervals instead.
+    fn signed_distance_interval(&self, v: [Interval; 3]) -> Interval {
+        let r = interval::radius(v);
+        if !r.is_finite() {
+            return Interval::entire();
+        }
+
+        let c = interval::centre(v);
+        let d = self.signed_distance(c);
+        let e = self.lipschitz(c, r) * r;
+        Interval::new(d - e, d + e)
+    }
+}
+
+// lets wrappers that are generic over their child, such as Transformed, take a boxed trait object
+// built up at runtime
+impl<S: ImplicitSurface + ?Sized> ImplicitSurface for Box<S> {
+    fn signed_distance(&self, v: Vec3) -> f64 {
+        (**self).signed_distance(v)
+    }
+
+    fn signed_distance_dual(&self, v: [Dual; 3]) -> Dual {
+        (**self).signed_distance_dual(v)
+    }
+
+    fn gradient(&self, v: Vec3) -> Vec3 {
+        (**self).gradient(v)
+    }
+
+    fn stencil(&self) -> Stencil {
+        (**self).stencil()
+    }
+
+    fn lipschitz(&self, v: Vec3, radius: f64) -> f64 {
+        (**self).lipschitz(v, radius)
+    }
+
+    fn signed_distance_interval(&self, v: [Interval; 3]) -> Interval {
+        (**self).signed_distance_interval(v)
+    }
 }
@@ -1,12 +1,23 @@
 use std::borrow::Borrow;
+use std::rc::Rc;
 
+use crate::csg;
+use crate::diagnostics::Diagnostics;
+use crate::dual::{self, Dual, Scalar};
 use crate::hittable::{HitRecord, Hittable, ImplicitSurface};
-use crate::ray::Ray;
+use crate::interval::Interval;
+use crate::material::{Lambertian, Material};
+use crate::ray::{MarchSettings, Ray};
+use crate::vec3::Vec3;
 
+// Each surface has a material, kept alongside it so the implicit surfaces can still be combined
+// as a list of fields
 #[derive(Default)]
 pub struct HittableList {
     parameteric_surfs: Vec<Box<dyn Hittable>>,
+    parameteric_materials: Vec<Rc<dyn Material>>,
     implicit_surfs: Vec<Box<dyn ImplicitSurface>>,
+    implicit_materials: Vec<Rc<dyn Material>>,
 }
 
 impl HittableList {
@@ -14,40 +25,76 @@ impl HittableList {
         Default::default()
     }
 
+    // objects added without a material are matte grey
     pub fn add(&mut self, object: Box<dyn Hittable>) {
-        self.parameteric_surfs.push(object);
+        self.add_with_material(object, Rc::new(Lambertian::default()));
     }
 
     pub fn add_implicit(&mut self, object: Box<dyn ImplicitSurface>) {
+        self.add_implicit_with_material(object, Rc::new(Lambertian::default()));
+    }
+
+    pub fn add_with_material(&mut self, object: Box<dyn Hittable>, material: Rc<dyn Material>) {
+        self.parameteric_surfs.push(object);
+        self.parameteric_materials.push(material);
+    }
+
+    pub fn add_implicit_with_material(
+        &mut self,
+        object: Box<dyn ImplicitSurface>,
+        material: Rc<dyn Material>,
+    ) {
         self.implicit_surfs.push(object);
+        self.implicit_materials.push(material);
+    }
+
+    pub fn implicit_surfaces(&self) -> &[Box<dyn ImplicitSurface>] {
+        &self.implicit_surfs
     }
 }
 
 impl HittableList {
-    pub fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    // Traces of the implicit surfaces are recorded in diagnostics, so failures can be told apart
+    // from misses
+    pub fn hit(
+        &self,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+        march: &MarchSettings,
+        diagnostics: &mut Diagnostics,
+    ) -> Option<HitRecord> {
         let mut hit_anything = false;
         let mut closest_so_far = t_max;
 
         let mut rec = HitRecord::new();
 
-        for object in &self.parameteric_surfs {
+        for (object, material) in self
+            .parameteric_surfs
+            .iter()
+            .zip(&self.parameteric_materials)
+        {
             match object.hit(ray, t_min, closest_so_far) {
                 None => {}
                 Some(trec) => {
                     hit_anything = true;
                     closest_so_far = trec.t;
                     rec = trec.clone();
+                    rec.material = Some(material.clone());
                 }
             }
         }
 
-        for object in &self.implicit_surfs {
-            match ray.trace(object.borrow(), t_min, closest_so_far) {
+        for (object, material) in self.implicit_surfs.iter().zip(&self.implicit_materials) {
+            let trace = ray.intersect(object.borrow(), t_min, closest_so_far, march);
+            diagnostics.record(&trace);
+            match trace.record {
                 None => {}
                 Some(trec) => {
                     hit_anything = true;
                     closest_so_far = trec.t;
                     rec = trec.clone();
+                    rec.material = Some(material.clone());
                 }
             }
         }
@@ -57,11 +104,47 @@ impl HittableList {
         }
         Some(rec)
     }
+
+    // the nearest hit on the parametric surfaces alone, for shading that finds the implicit ones
+    // from their field
+    pub fn hit_parametric(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut closest: Option<HitRecord> = None;
+        for object in &self.parameteric_surfs {
+            let t_max = closest.as_ref().map_or(t_max, |rec| rec.t);
+            if let Some(rec) = object.hit(ray, t_min, t_max) {
+                closest = Some(rec);
+            }
+        }
+        closest
+    }
+}
+
+// The implicit part of the world as a single field, i.e. the union of its implicit surfaces. The
+// parametric surfaces are left out since they have no distance function.
+impl ImplicitSurface for HittableList {
+    fn signed_distance(&self, v: Vec3) -> f64 {
+        csg::active(&self.implicit_surfs, v, |d, b| d < b).map_or(f64::INFINITY, |(_, d)| d)
+    }
+
+    fn signed_distance_dual(&self, v: [Dual; 3]) -> Dual {
+        csg::active(&self.implicit_surfs, dual::value(v), |d, b| d < b)
+            .map_or(Dual::constant(f64::INFINITY), |(c, _)| {
+                c.signed_distance_dual(v)
+            })
+    }
+
+    fn lipschitz(&self, v: Vec3, radius: f64) -> f64 {
+        csg::lipschitz(&self.implicit_surfs, v, radius)
+    }
+
+    fn signed_distance_interval(&self, v: [Interval; 3]) -> Interval {
+        csg::fold_interval(&self.implicit_surfs, v, Scalar::min, f64::INFINITY)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::f64::INFINITY;
+    use approx::assert_relative_eq;
 
     use crate::vec3::{eq, Point3};
     use crate::{sphere::Sphere, vec3::origin, vec3::unit_y};
@@ -80,8 +163,37 @@ mod tests {
         let expect = 4.0 * unit_y();
 
         let ray = Ray::new(origin(), unit_y());
-        let rec = world.hit(&ray, 0.0, INFINITY).unwrap();
+        let mut diagnostics = Diagnostics::default();
+        let rec = world
+            .hit(
+                &ray,
+                0.0,
+                f64::INFINITY,
+                &MarchSettings::default(),
+                &mut diagnostics,
+            )
+            .unwrap();
 
         assert!(eq(rec.p, expect));
+
+        // the second sphere is beyond the first hit
+        assert_eq!(diagnostics.hits, 1);
+        assert_eq!(diagnostics.misses, 1);
+        assert_eq!(diagnostics.failures(), 0);
+    }
+
+    #[test]
+    fn test_implicit_union() {
+        let mut world = HittableList::new();
+        assert_eq!(world.signed_distance(origin()), f64::INFINITY);
+
+        world.add_implicit(Box::new(Sphere::new(Point3::new(0.0, 5.0, 0.0), 1.0)));
+        world.add_implicit(Box::new(Sphere::new(Point3::new(0.0, 10.0, 0.0), 1.0)));
+
+        // parametric surfaces are not part of the field
+        world.add(Box::new(Sphere::new(origin(), 1.0)));
+
+        assert_relative_eq!(world.signed_distance(origin()), 4.0);
+        assert_relative_eq!(world.signed_distance(12.0 * unit_y()), 1.0);
     }
 }
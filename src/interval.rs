@@ -0,0 +1,376 @@
+use std::f64::consts::{FRAC_PI_2, PI, TAU};
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use crate::dual::Scalar;
+use crate::vec3::{Point3, Vec3};
+
+// Interval arithmetic. An Interval stands for every number between lo and hi, and every
+// operation on it gives an interval holding every result the operation could have on numbers
+// from its arguments. Run a distance function written against Scalar on intervals for x, y and z
+// and it bounds the function over that box: if the result doesn't hold zero, there is no
+// surface in the box.
+//
+// The bounds are conservative but not tight, since each use of a variable is taken to be
+// independent of the others (x - x gives [-w, w], not 0). They are not rounded outwards either,
+// so are only as exact as f64 arithmetic.
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Interval {
+    pub lo: f64,
+    pub hi: f64,
+}
+
+impl Interval {
+    pub fn new(lo: f64, hi: f64) -> Interval {
+        Interval { lo, hi }
+    }
+
+    pub fn point(x: f64) -> Interval {
+        Interval::new(x, x)
+    }
+
+    pub fn entire() -> Interval {
+        Interval::new(f64::NEG_INFINITY, f64::INFINITY)
+    }
+
+    pub fn width(&self) -> f64 {
+        self.hi - self.lo
+    }
+
+    pub fn mid(&self) -> f64 {
+        0.5 * (self.lo + self.hi)
+    }
+
+    // NaN bounds, e.g. from inf - inf, could be anything so are taken to hold x
+    pub fn contains(&self, x: f64) -> bool {
+        !(self.lo > x || self.hi < x)
+    }
+
+    // the smallest interval holding both
+    pub fn hull(self, o: Interval) -> Interval {
+        Interval::new(self.lo.min(o.lo), self.hi.max(o.hi))
+    }
+
+    // the halves either side of the middle
+    pub fn split(self) -> (Interval, Interval) {
+        let m = self.mid();
+        (Interval::new(self.lo, m), Interval::new(m, self.hi))
+    }
+
+    // apply a function increasing over the interval
+    fn increasing(self, f: fn(f64) -> f64) -> Interval {
+        Interval::new(f(self.lo), f(self.hi))
+    }
+
+    fn decreasing(self, f: fn(f64) -> f64) -> Interval {
+        Interval::new(f(self.hi), f(self.lo))
+    }
+
+    // apply sin or cos, given where it peaks, widening to the peaks and troughs inside
+    fn periodic(self, f: fn(f64) -> f64, peak: f64) -> Interval {
+        if self.width() >= TAU {
+            return Interval::new(-1.0, 1.0);
+        }
+
+        // whether peak + 2k pi lies in the interval for some k
+        let reaches = |peak: f64| peak + TAU * ((self.lo - peak) / TAU).ceil() <= self.hi;
+
+        let (a, b) = (f(self.lo), f(self.hi));
+        Interval::new(
+            if reaches(peak + PI) { -1.0 } else { a.min(b) },
+            if reaches(peak) { 1.0 } else { a.max(b) },
+        )
+    }
+}
+
+// 0 times infinity is taken to be 0, as the infinity only stands for some large number
+fn product(a: f64, b: f64) -> f64 {
+    if a == 0.0 || b == 0.0 {
+        0.0
+    } else {
+        a * b
+    }
+}
+
+impl Scalar for Interval {
+    fn constant(c: f64) -> Interval {
+        Interval::point(c)
+    }
+
+    fn value(self) -> f64 {
+        self.mid()
+    }
+
+    // the parts outside the domain are dropped
+    fn sqrt(self) -> Interval {
+        Interval::new(self.lo.max(0.0).sqrt(), self.hi.max(0.0).sqrt())
+    }
+
+    fn abs(self) -> Interval {
+        if self.lo >= 0.0 {
+            self
+        } else if self.hi <= 0.0 {
+            -self
+        } else {
+            Interval::new(0.0, self.hi.max(-self.lo))
+        }
+    }
+
+    fn sin(self) -> Interval {
+        self.periodic(f64::sin, FRAC_PI_2)
+    }
+
+    fn cos(self) -> Interval {
+        self.periodic(f64::cos, 0.0)
+    }
+
+    // anything at all if the interval reaches a pole
+    fn tan(self) -> Interval {
+        let pole = FRAC_PI_2 + PI * ((self.lo - FRAC_PI_2) / PI).ceil();
+        if self.width() >= PI || pole <= self.hi {
+            Interval::entire()
+        } else {
+            self.increasing(f64::tan)
+        }
+    }
+
+    fn asin(self) -> Interval {
+        self.clamp(-1.0, 1.0).increasing(f64::asin)
+    }
+
+    fn acos(self) -> Interval {
+        self.clamp(-1.0, 1.0).decreasing(f64::acos)
+    }
+
+    fn atan(self) -> Interval {
+        self.increasing(f64::atan)
+    }
+
+    fn exp(self) -> Interval {
+        self.increasing(f64::exp)
+    }
+
+    fn ln(self) -> Interval {
+        Interval::new(self.lo.max(0.0), self.hi.max(0.0)).increasing(f64::ln)
+    }
+
+    fn powf(self, n: f64) -> Interval {
+        if n == n.round() {
+            // integer powers are defined everywhere; even ones fold the negatives over
+            let p = if n.rem_euclid(2.0) == 0.0 {
+                let a = self.abs();
+                Interval::new(a.lo.powf(n.abs()), a.hi.powf(n.abs()))
+            } else {
+                Interval::new(self.lo.powf(n.abs()), self.hi.powf(n.abs()))
+            };
+            if n < 0.0 {
+                Interval::point(1.0) / p
+            } else {
+                p
+            }
+        } else {
+            let x = Interval::new(self.lo.max(0.0), self.hi.max(0.0));
+            let (a, b) = (x.lo.powf(n), x.hi.powf(n));
+            Interval::new(a.min(b), a.max(b))
+        }
+    }
+
+    fn min(self, other: Interval) -> Interval {
+        Interval::new(self.lo.min(other.lo), self.hi.min(other.hi))
+    }
+
+    fn max(self, other: Interval) -> Interval {
+        Interval::new(self.lo.max(other.lo), self.hi.max(other.hi))
+    }
+
+    fn sign(self) -> Interval {
+        Interval::new(self.lo.sign(), self.hi.sign())
+    }
+
+    // either branch unless the comparison comes out the same everywhere
+    fn select(self, other: Interval, a: Interval, b: Interval) -> Interval {
+        if self.hi < other.lo {
+            a
+        } else if self.lo >= other.hi {
+            b
+        } else {
+            a.hull(b)
+        }
+    }
+}
+
+impl Neg for Interval {
+    type Output = Interval;
+    fn neg(self) -> Interval {
+        Interval::new(-self.hi, -self.lo)
+    }
+}
+
+impl Add for Interval {
+    type Output = Interval;
+    fn add(self, o: Interval) -> Interval {
+        Interval::new(self.lo + o.lo, self.hi + o.hi)
+    }
+}
+
+impl Sub for Interval {
+    type Output = Interval;
+    fn sub(self, o: Interval) -> Interval {
+        Interval::new(self.lo - o.hi, self.hi - o.lo)
+    }
+}
+
+impl Mul for Interval {
+    type Output = Interval;
+    fn mul(self, o: Interval) -> Interval {
+        let p = [
+            product(self.lo, o.lo),
+            product(self.lo, o.hi),
+            product(self.hi, o.lo),
+            product(self.hi, o.hi),
+        ];
+        Interval::new(
+            p.iter().copied().fold(f64::INFINITY, f64::min),
+            p.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        )
+    }
+}
+
+// anything at all if the divisor could be zero
+impl Div for Interval {
+    type Output = Interval;
+    fn div(self, o: Interval) -> Interval {
+        if o.contains(0.0) {
+            Interval::entire()
+        } else {
+            self * Interval::new(1.0 / o.hi, 1.0 / o.lo)
+        }
+    }
+}
+
+impl Add<f64> for Interval {
+    type Output = Interval;
+    fn add(self, c: f64) -> Interval {
+        Interval::new(self.lo + c, self.hi + c)
+    }
+}
+
+impl Sub<f64> for Interval {
+    type Output = Interval;
+    fn sub(self, c: f64) -> Interval {
+        Interval::new(self.lo - c, self.hi - c)
+    }
+}
+
+impl Mul<f64> for Interval {
+    type Output = Interval;
+    fn mul(self, c: f64) -> Interval {
+        self * Interval::point(c)
+    }
+}
+
+impl Div<f64> for Interval {
+    type Output = Interval;
+    fn div(self, c: f64) -> Interval {
+        self / Interval::point(c)
+    }
+}
+
+// Boxes, as a point made of intervals
+
+pub fn bounds(min: Point3, max: Point3) -> [Interval; 3] {
+    [
+        Interval::new(min.x(), max.x()),
+        Interval::new(min.y(), max.y()),
+        Interval::new(min.z(), max.z()),
+    ]
+}
+
+pub fn centre(b: [Interval; 3]) -> Point3 {
+    Vec3::new(b[0].mid(), b[1].mid(), b[2].mid())
+}
+
+// half the length of the diagonal, the radius of the ball holding the box
+pub fn radius(b: [Interval; 3]) -> f64 {
+    0.5 * Vec3::new(b[0].width(), b[1].width(), b[2].width()).length()
+}
+
+#[cfg(test)]
+mod tests {
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_interval_arithmetic() {
+        let a = Interval::new(-1.0, 2.0);
+        let b = Interval::new(3.0, 4.0);
+
+        assert_eq!(a + b, Interval::new(2.0, 6.0));
+        assert_eq!(a - b, Interval::new(-5.0, -1.0));
+        assert_eq!(a * b, Interval::new(-4.0, 8.0));
+        assert_eq!(a / b, Interval::new(-1.0 / 3.0, 2.0 / 3.0));
+        assert_eq!(b / a, Interval::entire());
+
+        // every use is independent
+        assert_eq!(a - a, Interval::new(-3.0, 3.0));
+        assert_eq!(a.powf(2.0), Interval::new(0.0, 4.0));
+        assert_eq!(a * a, Interval::new(-2.0, 4.0));
+    }
+
+    #[test]
+    fn test_interval_functions_enclose() {
+        // the value of each function anywhere in the interval lies within its interval
+        type Pair = (fn(Interval) -> Interval, fn(f64) -> f64);
+        let fs: [Pair; 12] = [
+            (|x| x.sqrt(), |x| x.max(0.0).sqrt()),
+            (|x| x.abs(), f64::abs),
+            (|x| x.sin(), f64::sin),
+            (|x| x.cos(), f64::cos),
+            (|x| x.tan(), f64::tan),
+            (|x| x.atan(), f64::atan),
+            (|x| x.exp(), f64::exp),
+            (|x| x.ln(), f64::ln),
+            (|x| x.powf(3.0), |x| x.powf(3.0)),
+            (|x| x.powf(-2.0), |x| x.powf(-2.0)),
+            (|x| x.powf(0.5), |x| x.powf(0.5)),
+            (|x| x.sign(), |x| x.signum()),
+        ];
+
+        // intervals of a few widths starting all over [-5, 5], so some straddle zero and the
+        // turning points and poles of the trig functions
+        for i in 0..=40 {
+            let a = -5.0 + 0.26 * i as f64;
+            for width in [0.0, 0.1, 0.7, 1.6, 3.0] {
+                let b = a + width;
+                let x = Interval::new(a, b);
+                for (f, g) in fs {
+                    let y = f(x);
+                    for k in 0..=10 {
+                        let v = g(a + (b - a) * k as f64 / 10.0);
+                        // less whatever rounding there is, relative to large values such as 1 / x^2
+                        let e = 1e-12 * (1.0 + v.abs());
+                        assert!(v.is_nan() || (y.lo - e <= v && v <= y.hi + e));
+                    }
+                }
+            }
+        }
+
+        // and isn't wider than it needs to be
+        assert_eq!(Interval::new(0.0, PI).sin().lo, 0.0);
+        assert_eq!(Interval::new(0.0, PI).sin().hi, 1.0);
+        assert_eq!(Interval::new(1.0, 2.0).cos().hi, 1.0f64.cos());
+    }
+
+    #[test]
+    fn test_interval_select() {
+        let a = Interval::new(0.0, 1.0);
+        let b = Interval::new(2.0, 3.0);
+        assert_eq!(a.select(b, a, b), a);
+        assert_eq!(b.select(a, a, b), b);
+        assert_eq!(a.select(a, a, b), Interval::new(0.0, 3.0));
+        assert_eq!(
+            Scalar::min(a, Interval::new(0.5, 0.75)),
+            Interval::new(0.0, 0.75)
+        );
+    }
+}
@@ -0,0 +1,41 @@
+pub mod algebraic;
+pub mod blend;
+pub mod camera;
+pub mod capsule;
+pub mod colour;
+pub mod common;
+pub mod cone;
+pub mod csg;
+pub mod cuboid;
+pub mod cylinder;
+pub mod diagnostics;
+pub mod dual;
+pub mod dual_contouring;
+pub mod ellipsoid;
+pub mod equation;
+pub mod gradient;
+mod grid;
+pub mod hittable;
+pub mod hittable_list;
+pub mod interval;
+pub mod light;
+pub mod marching_cubes;
+pub mod material;
+pub mod mesh;
+pub mod obj;
+pub mod octree;
+pub mod plane;
+pub mod ply;
+pub mod polynomial;
+pub mod prism;
+mod profile;
+pub mod qef;
+pub mod ray;
+pub mod scene;
+pub mod settings;
+pub mod shading;
+pub mod sphere;
+pub mod stl;
+pub mod torus;
+pub mod transform;
+pub mod vec3;
@@ -0,0 +1,384 @@
+use std::f64::consts::PI;
+
+use crate::colour::Colour;
+use crate::common;
+use crate::diagnostics::Diagnostics;
+use crate::hittable::HitRecord;
+use crate::hittable_list::HittableList;
+use crate::material::Material;
+use crate::ray::{MarchSettings, Ray};
+use crate::vec3::{self, cross, dot, normalise, perpendicular, Point3, Vec3};
+
+// Points this close to a point or spot light get nothing from it, rather than an infinite amount
+// from no particular direction
+const MIN_DISTANCE: f64 = 1e-8;
+
+// Lights sampled directly from each diffuse hit, next event estimation, rather than waiting for a
+// scattered ray to happen upon them. Point, directional and spot lights are infinitely small so
+// can only be reached this way. Area lights can also be hit by scattered rays, so both ways of
+// finding them are weighted by multiple importance sampling (Veach 1997): each counts in
+// proportion to how likely it was to pick the direction, so whichever suits the material and
+// light best takes over and neither adds its noise.
+pub enum Light {
+    // colour is the intensity, falling off with the square of the distance
+    Point {
+        position: Point3,
+        colour: Colour,
+    },
+    // like the sun, shining along direction from infinitely far away, with irradiance colour
+    Directional {
+        direction: Vec3,
+        colour: Colour,
+    },
+    // a point light shining along direction, fully within inner of it and fading to nothing at
+    // outer, both the cosines of the angles from it
+    Spot {
+        position: Point3,
+        direction: Vec3,
+        inner: f64,
+        outer: f64,
+        colour: Colour,
+    },
+    // a ball whose surface gives off radiance colour
+    Area {
+        center: Point3,
+        radius: f64,
+        colour: Colour,
+    },
+}
+
+// Light reaching a point from a light: the unit direction towards it, how far away it is, the
+// radiance arriving, and the probability density, over solid angle, of picking that direction.
+// Lights that are a point or a direction are picked with certainty, so are delta.
+pub struct LightSample {
+    pub direction: Vec3,
+    pub distance: f64,
+    pub radiance: Colour,
+    pub pdf: f64,
+    pub delta: bool,
+}
+
+impl Light {
+    pub fn point(position: Point3, colour: Colour) -> Light {
+        Light::Point { position, colour }
+    }
+
+    pub fn directional(direction: Vec3, colour: Colour) -> Light {
+        Light::Directional {
+            direction: normalise(direction),
+            colour,
+        }
+    }
+
+    // the angles are the half angles of the cone, in radians
+    pub fn spot(
+        position: Point3,
+        direction: Vec3,
+        inner: f64,
+        outer: f64,
+        colour: Colour,
+    ) -> Light {
+        Light::Spot {
+            position,
+            direction: normalise(direction),
+            inner: inner.cos(),
+            outer: outer.cos(),
+            colour,
+        }
+    }
+
+    pub fn area(center: Point3, radius: f64, colour: Colour) -> Light {
+        Light::Area {
+            center,
+            radius,
+            colour,
+        }
+    }
+
+    // Some light arriving at p, or None if none can, e.g. from inside an area light or right at a
+    // point light
+    pub fn sample(&self, p: Point3) -> Option<LightSample> {
+        match *self {
+            Light::Point { position, colour } => {
+                let to = position - p;
+                let distance = to.length();
+                if distance <= MIN_DISTANCE {
+                    return None;
+                }
+                Some(LightSample {
+                    direction: to / distance,
+                    distance,
+                    radiance: colour / (distance * distance),
+                    pdf: 1.0,
+                    delta: true,
+                })
+            }
+            Light::Directional { direction, colour } => Some(LightSample {
+                direction: -direction,
+                distance: f64::INFINITY,
+                radiance: colour,
+                pdf: 1.0,
+                delta: true,
+            }),
+            Light::Spot {
+                position,
+                direction,
+                inner,
+                outer,
+                colour,
+            } => {
+                let to = position - p;
+                let distance = to.length();
+                if distance <= MIN_DISTANCE {
+                    return None;
+                }
+                let cos = dot(-to / distance, direction);
+                let falloff = smoothstep(outer, inner, cos);
+                if falloff == 0.0 {
+                    return None;
+                }
+                Some(LightSample {
+                    direction: to / distance,
+                    distance,
+                    radiance: (falloff / (distance * distance)) * colour,
+                    pdf: 1.0,
+                    delta: true,
+                })
+            }
+            // uniformly over the cone of directions the ball covers, seen from p
+            Light::Area {
+                center,
+                radius,
+                colour,
+            } => {
+                let cos_max = cone(p, center, radius)?;
+                let cos_theta = 1.0 + common::random_double() * (cos_max - 1.0);
+                let sin_theta = f64::sqrt(1.0 - cos_theta * cos_theta);
+                let phi = 2.0 * PI * common::random_double();
+
+                let w = normalise(center - p);
+                let u = perpendicular(w);
+                let v = cross(w, u);
+                let direction =
+                    sin_theta * phi.cos() * u + sin_theta * phi.sin() * v + cos_theta * w;
+
+                let distance = hit_ball(&Ray::new(p, direction), center, radius)?;
+                Some(LightSample {
+                    direction,
+                    distance,
+                    radiance: colour,
+                    pdf: 1.0 / (2.0 * PI * (1.0 - cos_max)),
+                    delta: false,
+                })
+            }
+        }
+    }
+
+    // where a ray meets the light and the radiance it sees there, which only area lights can be
+    pub fn hit(&self, r: &Ray) -> Option<(f64, Colour)> {
+        match *self {
+            Light::Area {
+                center,
+                radius,
+                colour,
+            } => hit_ball(r, center, radius).map(|t| (t, colour)),
+            _ => None,
+        }
+    }
+
+    // the density with which sample at p picks direction, which is zero for delta lights
+    pub fn pdf(&self, p: Point3, direction: Vec3) -> f64 {
+        match *self {
+            Light::Area { center, radius, .. } => {
+                match (
+                    cone(p, center, radius),
+                    hit_ball(&Ray::new(p, direction), center, radius),
+                ) {
+                    (Some(cos_max), Some(_)) => 1.0 / (2.0 * PI * (1.0 - cos_max)),
+                    _ => 0.0,
+                }
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+// the cosine of the half angle of the cone the ball covers seen from p, outside it
+fn cone(p: Point3, center: Point3, radius: f64) -> Option<f64> {
+    let d2 = (center - p).length_squared();
+    if d2 <= radius * radius {
+        return None;
+    }
+    Some(f64::sqrt(1.0 - radius * radius / d2))
+}
+
+// the nearest t ahead of the ray's origin where it meets the ball
+fn hit_ball(r: &Ray, center: Point3, radius: f64) -> Option<f64> {
+    let oc = center - r.origin();
+    let b = dot(oc, r.direction());
+    let discriminant = b * b - (oc.length_squared() - radius * radius);
+    if discriminant < 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    [b - root, b + root].into_iter().find(|t| *t > 0.0)
+}
+
+// 0 below a, 1 above b and smoothly between
+fn smoothstep(a: f64, b: f64, x: f64) -> f64 {
+    if b <= a {
+        return if x >= b { 1.0 } else { 0.0 };
+    }
+    let t = common::clamp((x - a) / (b - a), 0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+// How much a sample drawn with density a counts, when it could also have been drawn with
+// density b (Veach's power heuristic with exponent 2)
+pub fn power_heuristic(a: f64, b: f64) -> f64 {
+    let (a2, b2) = (a * a, b * b);
+    if a2 + b2 == 0.0 {
+        0.0
+    } else {
+        a2 / (a2 + b2)
+    }
+}
+
+// The light reaching the hit straight from each of the lights and scattered back along r_in, for
+// materials that can say how they scatter light from a given direction. Each light is sampled
+// once, with a shadow ray to see whether anything in the world is in the way.
+pub fn direct_lighting(
+    r_in: &Ray,
+    rec: &HitRecord,
+    material: &dyn Material,
+    world: &HittableList,
+    lights: &[Light],
+    march: &MarchSettings,
+    diagnostics: &mut Diagnostics,
+) -> Colour {
+    let mut total = Colour::new(0.0, 0.0, 0.0);
+
+    for light in lights {
+        let Some(sample) = light.sample(rec.p) else {
+            continue;
+        };
+        let Some((f, scatter_pdf)) = material.evaluate(r_in, rec, sample.direction) else {
+            continue;
+        };
+        if vec3::eq(f, Colour::new(0.0, 0.0, 0.0)) {
+            continue;
+        }
+
+        let shadow = rec.spawn(sample.direction, march);
+        if world
+            .hit(&shadow, 0.0, sample.distance, march, diagnostics)
+            .is_some()
+        {
+            continue;
+        }
+
+        let weight = if sample.delta {
+            1.0
+        } else {
+            power_heuristic(sample.pdf, scatter_pdf)
+        };
+        total += (weight / sample.pdf) * (f * sample.radiance);
+    }
+
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::vec3::{origin, unit_x, unit_y};
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_light_point_and_spot() {
+        let white = Colour::new(1.0, 1.0, 1.0);
+
+        let s = Light::point(2.0 * unit_y(), white)
+            .sample(origin())
+            .unwrap();
+        assert!(vec3::eq(s.direction, unit_y()));
+        assert_relative_eq!(s.distance, 2.0);
+        assert_relative_eq!(s.radiance.x(), 0.25);
+
+        // full inside the inner cone, none outside the outer one
+        let spot = Light::spot(2.0 * unit_y(), -unit_y(), 0.2, 0.4, white);
+        assert_relative_eq!(spot.sample(origin()).unwrap().radiance.x(), 0.25);
+        assert!(spot.sample(2.0 * unit_x()).is_none());
+        let edge = spot.sample(2.0 * 0.3f64.tan() * unit_x()).unwrap();
+        assert!(edge.radiance.x() > 0.0 && edge.radiance.x() < 0.25);
+
+        // and nothing at the lights themselves
+        assert!(Light::point(2.0 * unit_y(), white)
+            .sample(2.0 * unit_y())
+            .is_none());
+        assert!(spot.sample(2.0 * unit_y()).is_none());
+    }
+
+    #[test]
+    fn test_light_area() {
+        let light = Light::area(4.0 * unit_y(), 1.0, Colour::new(2.0, 2.0, 2.0));
+        assert!(light.sample(4.0 * unit_y()).is_none());
+
+        for _ in 0..100 {
+            let s = light.sample(origin()).unwrap();
+            assert!(!s.delta);
+            assert!(s.distance >= 3.0 && s.distance <= 4.0);
+
+            // the directions it picks are those that hit it, with the density pdf gives
+            let r = Ray::new(origin(), s.direction);
+            let (t, radiance) = light.hit(&r).unwrap();
+            assert_relative_eq!(t, s.distance, epsilon = 1e-9);
+            assert_relative_eq!(radiance.x(), 2.0);
+            assert_relative_eq!(light.pdf(origin(), s.direction), s.pdf);
+        }
+        assert_eq!(light.pdf(origin(), unit_x()), 0.0);
+    }
+
+    #[test]
+    fn test_light_direct_lighting() {
+        // a matte floor at the origin lit from straight above
+        let r = Ray::new(unit_y() - unit_x(), Vec3::new(1.0, -1.0, 0.0));
+        let mut rec = HitRecord::new();
+        rec.set_face_normal(&r, unit_y());
+        let floor = Lambertian::new(Colour::new(0.5, 0.5, 0.5));
+        let lights = [Light::point(2.0 * unit_y(), Colour::new(4.0, 4.0, 4.0))];
+        let march = MarchSettings::default();
+
+        let lit = |world: &HittableList| {
+            direct_lighting(
+                &r,
+                &rec,
+                &floor,
+                world,
+                &lights,
+                &march,
+                &mut Diagnostics::default(),
+            )
+        };
+
+        // albedo / pi times the irradiance, 4 / 2^2
+        assert_relative_eq!(lit(&HittableList::new()).x(), 0.5 / PI);
+
+        // and nothing with a ball in the way
+        let mut world = HittableList::new();
+        world.add_implicit(Box::new(Sphere::new(unit_y(), 0.5)));
+        assert_eq!(lit(&world).x(), 0.0);
+    }
+
+    #[test]
+    fn test_light_power_heuristic() {
+        assert_relative_eq!(power_heuristic(1.0, 1.0), 0.5);
+        assert_relative_eq!(power_heuristic(3.0, 1.0) + power_heuristic(1.0, 3.0), 1.0);
+        assert_eq!(power_heuristic(0.0, 0.0), 0.0);
+    }
+}
@@ -1,46 +1,173 @@
-mod camera;
-mod colour;
-mod common;
-mod cylinder;
-mod hittable;
-mod hittable_list;
-mod ray;
-mod sphere;
-mod vec3;
-use anyhow::Result;
+use anyhow::{bail, Result};
 use clap::Parser;
 
-mod settings;
-
-use settings::Settings;
+use implicit_surface_gen::settings::{
+    self, Encoding, MeshFile, MeshMethod, Settings, VertexAttribute,
+};
 
 use std::fs;
-use std::io::Write;
-
-use camera::Camera;
-use colour::Colour;
-use cylinder::Cylinder;
-use hittable_list::HittableList;
-use ray::Ray;
-use sphere::Sphere;
-use vec3::{unit_x, unit_y, unit_z, Point3};
-
-fn ray_color(r: &Ray, world: &HittableList, depth: u64) -> Colour {
+use std::io::{self, Write};
+
+use implicit_surface_gen::colour::{self, Colour};
+use implicit_surface_gen::common;
+use implicit_surface_gen::cylinder::Cylinder;
+use implicit_surface_gen::diagnostics::Diagnostics;
+use implicit_surface_gen::dual_contouring;
+use implicit_surface_gen::gradient::{self, Stencil};
+use implicit_surface_gen::hittable::ImplicitSurface;
+use implicit_surface_gen::hittable_list::HittableList;
+use implicit_surface_gen::light::{self, Light};
+use implicit_surface_gen::marching_cubes;
+use implicit_surface_gen::mesh::Mesh;
+use implicit_surface_gen::obj;
+use implicit_surface_gen::octree::Octree;
+use implicit_surface_gen::ply;
+use implicit_surface_gen::ray::{MarchSettings, Ray};
+use implicit_surface_gen::scene;
+use implicit_surface_gen::shading::{self, ShadingMode};
+use implicit_surface_gen::sphere::Sphere;
+use implicit_surface_gen::stl;
+use implicit_surface_gen::vec3::{unit_y, Point3, Vec3};
+
+// The light arriving back along r. Lights are sampled directly at diffuse hits, so where a
+// scattered ray happens upon an area light its light is weighted against the chance of having
+// sampled it that way; scatter_pdf is the density with which the ray was scattered, or None for
+// camera rays and mirror or glass bounces, which lights can't be sampled for.
+fn ray_color(
+    r: &Ray,
+    world: &HittableList,
+    lights: &[Light],
+    march: &MarchSettings,
+    diagnostics: &mut Diagnostics,
+    depth: u64,
+    scatter_pdf: Option<f64>,
+) -> Colour {
     if depth == 0 {
         return Colour::new(0.0, 0.0, 0.0);
     }
 
-    match world.hit(r, 0.0, f64::INFINITY) {
-        None => {}
-        Some(rec) => {
-            let direction = rec.normal + (0.5 * vec3::random_unit_vector());
-            return 0.5 * ray_color(&Ray::new(rec.p, direction), world, depth - 1);
-        }
+    let rec = world.hit(r, 0.0, f64::INFINITY, march, diagnostics);
+    let t_max = rec.as_ref().map_or(f64::INFINITY, |rec| rec.t);
+
+    // an area light in front of whatever the ray hits in the world
+    let light = lights
+        .iter()
+        .filter_map(|l| l.hit(r).map(|(t, radiance)| (t, radiance, l)))
+        .filter(|(t, _, _)| *t < t_max)
+        .min_by(|a, b| a.0.total_cmp(&b.0));
+    if let Some((_, radiance, l)) = light {
+        return match scatter_pdf {
+            None => radiance,
+            Some(p) => light::power_heuristic(p, l.pdf(r.origin(), r.direction())) * radiance,
+        };
+    }
+
+    if let Some(rec) = rec {
+        let material = rec
+            .material
+            .as_ref()
+            .expect("every hit in the world has a material");
+        let emitted = material.emitted(&rec);
+        return match material.scatter(r, &rec) {
+            None => emitted,
+            Some((attenuation, direction)) => {
+                let direct = light::direct_lighting(
+                    r,
+                    &rec,
+                    material.as_ref(),
+                    world,
+                    lights,
+                    march,
+                    diagnostics,
+                );
+                let scatter_pdf = material.evaluate(r, &rec, direction).map(|(_, pdf)| pdf);
+                emitted
+                    + direct
+                    + attenuation
+                        * ray_color(
+                            &rec.spawn(direction, march),
+                            world,
+                            lights,
+                            march,
+                            diagnostics,
+                            depth - 1,
+                            scatter_pdf,
+                        )
+            }
+        };
+    }
+
+    shading::sky(r.direction())
+}
+
+fn polygonize(world: &HittableList, m: &settings::Mesh) -> Mesh {
+    let min = Point3::new(m.min[0], m.min[1], m.min[2]);
+    let max = Point3::new(m.max[0], m.max[1], m.max[2]);
+
+    if m.adaptive {
+        let depth = m.resolution.next_power_of_two().trailing_zeros();
+        let tree = Octree::new(world, min, max, depth);
+        return match m.method {
+            MeshMethod::MarchingCubes => marching_cubes::polygonize_octree(world, &tree),
+            MeshMethod::DualContouring => dual_contouring::polygonize_octree(world, &tree),
+        };
+    }
+
+    match m.method {
+        MeshMethod::MarchingCubes => marching_cubes::polygonize(world, min, max, m.resolution),
+        MeshMethod::DualContouring => dual_contouring::polygonize(world, min, max, m.resolution),
+    }
+}
+
+fn write_mesh(path: &str, mesh: &Mesh, file: MeshFile, format: &Encoding) -> Result<()> {
+    let mut out = io::BufWriter::new(fs::File::create(path)?);
+    match (file, format) {
+        (MeshFile::Stl, Encoding::Ascii) => stl::write_ascii(&mut out, mesh, "implicit")?,
+        (MeshFile::Stl, Encoding::Binary) => stl::write_binary(&mut out, mesh, "implicit")?,
+        (MeshFile::Obj, _) => obj::write(&mut out, mesh, "implicit")?,
+        (MeshFile::Ply, Encoding::Ascii) => ply::write_ascii(&mut out, mesh)?,
+        (MeshFile::Ply, Encoding::Binary) => ply::write_binary(&mut out, mesh)?,
+    }
+    out.flush()?;
+    Ok(())
+}
+
+// Report the largest angle between the analytic and numeric gradients of the built in sphere and
+// cylinder, each implicit surface in the scene, and the scene as a whole, over random points in
+// the box around where the camera looks
+fn check_gradients(world: &HittableList, centre: Point3, epsilon: f64) -> Result<()> {
+    if epsilon.is_nan() || epsilon <= 0.0 {
+        bail!("--epsilon must be positive, got {}", epsilon);
     }
 
-    let n = vec3::normalise(r.direction());
-    let t = 0.5 * (n.y() + 1.0);
-    (1.0 - t) * Colour::new(1.0, 1.0, 1.0) + t * Colour::new(0.5, 0.7, 1.0)
+    let points: Vec<Point3> = (0..1000)
+        .map(|_| centre + 2.0 * Vec3::random_range(-1.0, 1.0))
+        .collect();
+
+    let sphere = Sphere::new(centre, 0.75);
+    let cylinder = Cylinder::new(centre, unit_y(), 0.25);
+    let mut surfaces: Vec<(String, &dyn ImplicitSurface)> = vec![
+        ("sphere".to_string(), &sphere),
+        ("cylinder".to_string(), &cylinder),
+    ];
+    for (i, s) in world.implicit_surfaces().iter().enumerate() {
+        surfaces.push((format!("surface {}", i), s.as_ref()));
+    }
+    surfaces.push(("scene".to_string(), world));
+
+    for (name, s) in surfaces {
+        for stencil in [
+            Stencil::Central { epsilon },
+            Stencil::Tetrahedral { epsilon },
+        ] {
+            let e = gradient::max_angular_error(s, &points, stencil);
+            eprintln!(
+                "{:<10} {:?}: max angular error {:.3e} rad",
+                name, stencil, e
+            );
+        }
+    }
+    Ok(())
 }
 
 fn main() -> Result<()> {
@@ -48,31 +175,48 @@ fn main() -> Result<()> {
 
     let cfg = Settings::new(&args.config)?;
 
-    // let open a file
-    let mut file = fs::File::create(cfg.output)?;
-
     // World
 
-    let mut world = HittableList::new();
+    let world = scene::build(&cfg.objects)?;
+    let lights = scene::lights(&cfg.lights)?;
 
-    // implictly defined sphere in the middle
-    world.add_implicit(Box::new(Sphere::new(Point3::new(0.0, 0.0, -2.0), 0.75)));
-    world.add_implicit(Box::new(Cylinder::new(
-        Point3::new(0.0, 0.0, -1.0),
-        unit_y(),
-        0.25,
-    )));
+    if args.check_gradients {
+        let [x, y, z] = cfg.camera.look_at;
+        return check_gradients(&world, Point3::new(x, y, z), args.epsilon);
+    }
 
-    // explicitly defined sphere as the floor
-    world.add(Box::new(Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0)));
+    // Camera, built before anything is written so that a bad one stops us early
 
-    // Camera
+    let aspect_ratio = cfg.view.width as f64 / cfg.view.height as f64;
+    let cam = cfg.camera.camera(aspect_ratio, &world, &cfg.marching)?;
 
-    let cam = Camera::new();
+    // let open a file
+    let mut file = fs::File::create(cfg.output)?;
+
+    // Mesh
+
+    if let Some(m) = &cfg.mesh {
+        eprintln!("Polygonizing to {}", m.output);
+        let mut mesh = polygonize(&world, m);
+
+        for a in &m.attributes {
+            match a {
+                VertexAttribute::Distance => {
+                    mesh.add_attribute("distance", |p, _| world.signed_distance(p))
+                }
+            }
+        }
+
+        let file = m
+            .file()
+            .expect("the mesh output was checked when the settings were loaded");
+        write_mesh(&m.output, &mesh, file, &m.format)?;
+    }
 
     _ = file.write(format!("P3\n{} {}\n255\n", cfg.view.width, cfg.view.height).as_bytes())?;
 
     let samples_per_pixel = cfg.sampling.samples_per_pixel;
+    let mut diagnostics = Diagnostics::default();
     for j in (0..cfg.view.height).rev() {
         eprint!("\rScanlines remaining {}", j);
         for i in 0..cfg.view.width {
@@ -81,11 +225,31 @@ fn main() -> Result<()> {
                 let u = (i as f64 + common::random_double()) / (cfg.view.width - 1) as f64;
                 let v = (j as f64 + common::random_double()) / (cfg.view.height - 1) as f64;
                 let r = cam.get_ray(u, v);
-                pixel_color += ray_color(&r, &world, cfg.sampling.max_depth);
+                pixel_color += match cfg.shading.mode {
+                    ShadingMode::PathTraced => ray_color(
+                        &r,
+                        &world,
+                        &lights,
+                        &cfg.marching,
+                        &mut diagnostics,
+                        cfg.sampling.max_depth,
+                        None,
+                    ),
+                    ShadingMode::Preview => shading::preview(
+                        &r,
+                        &world,
+                        &lights,
+                        &cfg.marching,
+                        &cfg.shading,
+                        &mut diagnostics,
+                    ),
+                };
             }
             colour::write_color(&mut file, pixel_color, samples_per_pixel);
         }
     }
 
+    eprintln!("\n{}", diagnostics);
+
     Ok(())
 }
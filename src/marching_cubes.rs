@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+
+use crate::grid::{SampleGrid, EDGES};
+use crate::hittable::ImplicitSurface;
+use crate::mesh::Mesh;
+use crate::octree::Octree;
+use crate::vec3::{normalise, Point3};
+
+// the corners of each face of a cell, in order around the face
+const FACES: [[usize; 4]; 6] = [
+    [0, 2, 6, 4],
+    [1, 3, 7, 5],
+    [0, 1, 5, 4],
+    [2, 3, 7, 6],
+    [0, 1, 3, 2],
+    [4, 5, 7, 6],
+];
+
+fn edge_between(a: usize, b: usize) -> usize {
+    EDGES
+        .iter()
+        .position(|&(u, v)| (u, v) == (a, b) || (u, v) == (b, a))
+        .expect("corners share an edge")
+}
+
+// The contour loops through a cell for each arrangement of inside corners, given as the cell edges
+// they cross. Rather than the usual hand-built 256 case table we derive the loops by joining up
+// the crossings on each face. Faces with four crossings are ambiguous; we always cut off the
+// inside corners, and since neighbouring cells make the same choice on their shared face the
+// resulting mesh has no cracks.
+fn contour_loops(inside: u8) -> Vec<Vec<usize>> {
+    let is_in = |c: usize| inside & (1 << c) != 0;
+    let mut links: [Vec<usize>; 12] = Default::default();
+
+    for face in FACES {
+        let edges: Vec<usize> = (0..4)
+            .map(|k| edge_between(face[k], face[(k + 1) % 4]))
+            .collect();
+        let crossed = |k: usize| is_in(face[k]) != is_in(face[(k + 1) % 4]);
+
+        let active: Vec<usize> = (0..4).filter(|&k| crossed(k)).collect();
+        let mut join = |a: usize, b: usize| {
+            links[edges[a]].push(edges[b]);
+            links[edges[b]].push(edges[a]);
+        };
+
+        match active.len() {
+            2 => join(active[0], active[1]),
+            4 => {
+                for k in (0..4).filter(|&k| is_in(face[k])) {
+                    join((k + 3) % 4, k);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut loops = vec![];
+    let mut visited = [false; 12];
+    for start in 0..12 {
+        if visited[start] || links[start].is_empty() {
+            continue;
+        }
+
+        let mut lp = vec![start];
+        visited[start] = true;
+        let (mut prev, mut cur) = (start, links[start][0]);
+        while cur != start {
+            lp.push(cur);
+            visited[cur] = true;
+            let next = if links[cur][0] == prev {
+                links[cur][1]
+            } else {
+                links[cur][0]
+            };
+            (prev, cur) = (cur, next);
+        }
+        loops.push(lp);
+    }
+    loops
+}
+
+// Polygonize the zero set of a surface within the box min-max using marching cubes. Resolution is
+// the number of cells along the longest side of the box. Vertices are shared between neighbouring
+// cells and take their normals from the surface gradient.
+pub fn polygonize(
+    surface: &dyn ImplicitSurface,
+    min: Point3,
+    max: Point3,
+    resolution: usize,
+) -> Mesh {
+    contour(surface, &SampleGrid::new(surface, min, max, resolution))
+}
+
+// As polygonize, but only visiting the leaves of an octree rather than every cell of the box
+pub fn polygonize_octree(surface: &dyn ImplicitSurface, tree: &Octree) -> Mesh {
+    contour(surface, &SampleGrid::from_octree(surface, tree))
+}
+
+fn contour(surface: &dyn ImplicitSurface, grid: &SampleGrid) -> Mesh {
+    let table: Vec<Vec<Vec<usize>>> = (0..=255).map(contour_loops).collect();
+
+    let mut mesh = Mesh::new();
+    let mut edge_vertex: HashMap<usize, usize> = HashMap::new();
+
+    for &c in &grid.active {
+        let mut inside = 0u8;
+        for i in 0..8 {
+            if grid.value(SampleGrid::corner(c, i)) < 0.0 {
+                inside |= 1 << i;
+            }
+        }
+
+        for lp in &table[inside as usize] {
+            let ids: Vec<usize> = lp
+                .iter()
+                .map(|&e| {
+                    let (a, b) = EDGES[e];
+                    *edge_vertex
+                        .entry(grid.edge_key(c, a, b))
+                        .or_insert_with(|| edge_crossing(grid, surface, &mut mesh, c, a, b))
+                })
+                .collect();
+
+            for i in 1..ids.len() - 1 {
+                mesh.add_triangle(ids[0], ids[i], ids[i + 1]);
+            }
+        }
+    }
+
+    mesh
+}
+
+// add a vertex where the surface crosses the edge a-b of cell c, interpolating the samples
+fn edge_crossing(
+    grid: &SampleGrid,
+    surface: &dyn ImplicitSurface,
+    mesh: &mut Mesh,
+    c: [usize; 3],
+    a: usize,
+    b: usize,
+) -> usize {
+    let (ca, cb) = (SampleGrid::corner(c, a), SampleGrid::corner(c, b));
+    let (da, db) = (grid.value(ca), grid.value(cb));
+    let (pa, pb) = (grid.point(ca), grid.point(cb));
+
+    let p = pa + (da / (da - db)) * (pb - pa);
+    mesh.add_vertex(p, normalise(surface.gradient(p)))
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::csg::Union;
+    use crate::cuboid::Cuboid;
+    use crate::equation::Equation;
+    use crate::sphere::Sphere;
+    use crate::vec3::{dot, origin, unit_x, Vec3};
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_contour_loops() {
+        // nothing inside, or everything, gives no surface
+        assert!(contour_loops(0).is_empty());
+        assert!(contour_loops(255).is_empty());
+
+        // a single corner is cut off by a triangle
+        let mut lp = contour_loops(1).remove(0);
+        lp.sort();
+        assert_eq!(lp, vec![0, 4, 8]);
+
+        // half the cube is cut by a quad
+        assert_eq!(contour_loops(0b0101_0101)[0].len(), 4);
+
+        // opposite corners of a face are cut off separately
+        assert_eq!(contour_loops(0b0000_1001).len(), 2);
+    }
+
+    #[test]
+    fn test_polygonize_sphere() {
+        let s = Sphere::new(origin(), 1.0);
+        let lo = Vec3::new(-1.5, -1.5, -1.5);
+        let mesh = polygonize(&s, lo, -lo, 16);
+
+        assert!(!mesh.triangles.is_empty());
+        assert!(mesh.is_closed());
+
+        // vertices are interpolated so sit close to the surface
+        for (p, n) in mesh.vertices.iter().zip(&mesh.normals) {
+            assert!(s.signed_distance(*p).abs() < 0.02);
+            assert_relative_eq!(dot(*n, normalise(*p)), 1.0, epsilon = 1e-9);
+        }
+
+        // and triangles face outwards
+        for t in &mesh.triangles {
+            assert!(dot(mesh.facet_normal(*t), mesh.vertices[t[0]]) > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_polygonize_disjoint() {
+        // two pieces that each need their own shell, and a box that isn't a cube
+        let u = Union::new(
+            Box::new(Sphere::new(-2.0 * unit_x(), 0.5)),
+            Box::new(Cuboid::new(2.0 * unit_x(), Vec3::new(0.5, 0.5, 0.5))),
+        );
+        let mesh = polygonize(
+            &u,
+            Vec3::new(-3.0, -1.0, -1.0),
+            Vec3::new(3.0, 1.0, 1.0),
+            30,
+        );
+        assert!(mesh.is_closed());
+        assert!(mesh.vertices.iter().any(|p| p.x() < 0.0));
+        assert!(mesh.vertices.iter().any(|p| p.x() > 0.0));
+    }
+
+    #[test]
+    fn test_polygonize_octree_matches_grid() {
+        let s = Sphere::new(origin(), 1.0);
+        let lo = Vec3::new(-1.5, -1.5, -1.5);
+
+        // the finest level of the octree has the same cells as the uniform grid
+        let uniform = polygonize(&s, lo, -lo, 32);
+        let adaptive = polygonize_octree(&s, &Octree::new(&s, lo, -lo, 5));
+
+        assert!(adaptive.is_closed());
+        assert_eq!(adaptive.vertices.len(), uniform.vertices.len());
+        assert_eq!(adaptive.triangles.len(), uniform.triangles.len());
+    }
+
+    #[test]
+    fn test_polygonize_octree_equation() {
+        // a sphere as an equation, whose gradient 2 |v| is at most 3 sqrt(3) in the box; its value
+        // outside the sphere is more than the distance, which would prune cells it passes through
+        let e = Equation::parse("x^2 + y^2 + z^2 - 1", &HashMap::new())
+            .unwrap()
+            .with_lipschitz(5.2);
+        let lo = Vec3::new(-1.5, -1.5, -1.5);
+
+        let tree = Octree::new(&e, lo, -lo, 5);
+        let leaves = tree.leaves();
+        assert!(leaves.len() < 32 * 32 * 32 / 4);
+
+        let uniform = SampleGrid::new(&e, lo, -lo, 32);
+        for c in &uniform.active {
+            let inside = (0..8).filter(|&i| uniform.value(SampleGrid::corner(*c, i)) < 0.0);
+            if !matches!(inside.count(), 0 | 8) {
+                assert!(leaves.contains(c));
+            }
+        }
+
+        let adaptive = polygonize_octree(&e, &tree);
+        assert!(adaptive.is_closed());
+        assert_eq!(
+            adaptive.triangles.len(),
+            polygonize(&e, lo, -lo, 32).triangles.len()
+        );
+    }
+}
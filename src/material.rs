@@ -0,0 +1,265 @@
+use std::f64::consts::PI;
+
+use crate::colour::Colour;
+use crate::common;
+use crate::hittable::HitRecord;
+use crate::ray::Ray;
+use crate::vec3::{self, dot, normalise, reflect, refract, Vec3};
+
+// What a surface does with the light that reaches it. At a hit a material may scatter the ray,
+// giving the direction it carries on in and how much of each colour survives, or absorb it, and
+// may also give off light of its own. The direction is only a direction; the caller spawns the
+// ray from the hit, so it starts clear of the surface.
+pub trait Material {
+    // the attenuation and direction of the scattered ray, or None if the ray is absorbed
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Colour, Vec3)>;
+
+    // how much of each colour survives scattering, whichever way, for shading that doesn't
+    // follow rays; mid grey for materials that don't say
+    fn albedo(&self) -> Colour {
+        Colour::new(0.5, 0.5, 0.5)
+    }
+
+    // the light given off at the hit, which most materials have none of
+    fn emitted(&self, _rec: &HitRecord) -> Colour {
+        Colour::new(0.0, 0.0, 0.0)
+    }
+
+    // For materials that scatter light every which way, how much arriving from direction is
+    // scattered back along r_in (the BSDF times the cosine to the normal), and the density over
+    // solid angle with which scatter would pick that direction, so lights can be sampled directly.
+    // Mirrors and glass only send light on in one direction, so give None.
+    fn evaluate(&self, _r_in: &Ray, _rec: &HitRecord, _direction: Vec3) -> Option<(Colour, f64)> {
+        None
+    }
+}
+
+// Matte, scattering in proportion to the cosine of the angle to the normal
+pub struct Lambertian {
+    albedo: Colour,
+}
+
+impl Lambertian {
+    pub fn new(albedo: Colour) -> Lambertian {
+        Lambertian { albedo }
+    }
+}
+
+// the grey every object was before it could be given a material
+impl Default for Lambertian {
+    fn default() -> Self {
+        Lambertian::new(Colour::new(0.5, 0.5, 0.5))
+    }
+}
+
+impl Material for Lambertian {
+    fn scatter(&self, _r_in: &Ray, rec: &HitRecord) -> Option<(Colour, Vec3)> {
+        let direction = rec.normal + vec3::random_unit_vector();
+
+        // a random vector nearly opposite the normal would leave almost nothing to go on
+        if direction.length_squared() < 1e-16 {
+            return Some((self.albedo, rec.normal));
+        }
+        Some((self.albedo, direction))
+    }
+
+    fn albedo(&self) -> Colour {
+        self.albedo
+    }
+
+    // scatter picks directions with density cos / pi, and the BSDF is albedo / pi
+    fn evaluate(&self, _r_in: &Ray, rec: &HitRecord, direction: Vec3) -> Option<(Colour, f64)> {
+        let cos = f64::max(dot(normalise(direction), rec.normal), 0.0);
+        Some((self.albedo * (cos / PI), cos / PI))
+    }
+}
+
+// Mirror-like, reflecting about the normal then blurred by a random offset up to fuzz
+pub struct Metal {
+    albedo: Colour,
+    fuzz: f64,
+}
+
+impl Metal {
+    pub fn new(albedo: Colour, fuzz: f64) -> Metal {
+        Metal {
+            albedo,
+            fuzz: fuzz.min(1.0),
+        }
+    }
+}
+
+impl Material for Metal {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Colour, Vec3)> {
+        let reflected = reflect(r_in.direction(), rec.normal);
+        let direction = reflected + self.fuzz * vec3::random_in_unit_sphere();
+
+        // blurred to below the surface, the ray is absorbed
+        if dot(direction, rec.normal) > 0.0 {
+            Some((self.albedo, direction))
+        } else {
+            None
+        }
+    }
+
+    fn albedo(&self) -> Colour {
+        self.albedo
+    }
+}
+
+// Glass and the like, refracting where it can and otherwise reflecting, with Schlick's
+// approximation for how much is reflected at each angle
+pub struct Dielectric {
+    refractive_index: f64,
+}
+
+impl Dielectric {
+    pub fn new(refractive_index: f64) -> Dielectric {
+        Dielectric { refractive_index }
+    }
+
+    fn reflectance(cosine: f64, ratio: f64) -> f64 {
+        let r0 = ((1.0 - ratio) / (1.0 + ratio)).powi(2);
+        r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+    }
+}
+
+impl Material for Dielectric {
+    // Rays from inside an implicit surface hit its back face, so front_face says which way we're
+    // going through it
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Colour, Vec3)> {
+        let ratio = if rec.front_face {
+            1.0 / self.refractive_index
+        } else {
+            self.refractive_index
+        };
+
+        let unit = normalise(r_in.direction());
+        let cos_theta = f64::min(dot(-unit, rec.normal), 1.0);
+        let sin_theta = f64::sqrt(1.0 - cos_theta * cos_theta);
+
+        let direction = if ratio * sin_theta > 1.0
+            || Dielectric::reflectance(cos_theta, ratio) > common::random_double()
+        {
+            reflect(unit, rec.normal)
+        } else {
+            refract(unit, rec.normal, ratio)
+        };
+
+        Some((Colour::new(1.0, 1.0, 1.0), direction))
+    }
+
+    fn albedo(&self) -> Colour {
+        Colour::new(1.0, 1.0, 1.0)
+    }
+}
+
+// A light, giving off its colour and scattering nothing
+pub struct Emissive {
+    colour: Colour,
+}
+
+impl Emissive {
+    pub fn new(colour: Colour) -> Emissive {
+        Emissive { colour }
+    }
+}
+
+impl Material for Emissive {
+    fn scatter(&self, _r_in: &Ray, _rec: &HitRecord) -> Option<(Colour, Vec3)> {
+        None
+    }
+
+    fn albedo(&self) -> Colour {
+        Colour::new(0.0, 0.0, 0.0)
+    }
+
+    fn emitted(&self, _rec: &HitRecord) -> Colour {
+        self.colour
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::vec3::{eq, origin, unit_x, unit_y};
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    // a hit at the origin on a floor facing up, by a ray coming down at 45 degrees
+    fn floor_hit() -> (Ray, HitRecord) {
+        let r = Ray::new(Vec3::new(-1.0, 1.0, 0.0), Vec3::new(1.0, -1.0, 0.0));
+        let mut rec = HitRecord::new();
+        rec.p = origin();
+        rec.t = 2.0f64.sqrt();
+        rec.set_face_normal(&r, unit_y());
+        (r, rec)
+    }
+
+    #[test]
+    fn test_material_lambertian() {
+        let (r, rec) = floor_hit();
+        let m = Lambertian::new(Colour::new(0.1, 0.2, 0.3));
+        for _ in 0..100 {
+            let (attenuation, d) = m.scatter(&r, &rec).unwrap();
+            assert!(eq(attenuation, Colour::new(0.1, 0.2, 0.3)));
+            assert!(dot(d, rec.normal) >= 0.0);
+        }
+        assert!(eq(m.albedo(), Colour::new(0.1, 0.2, 0.3)));
+        assert!(eq(m.emitted(&rec), Colour::new(0.0, 0.0, 0.0)));
+    }
+
+    // a material that says nothing but how it scatters
+    struct Plain;
+
+    impl Material for Plain {
+        fn scatter(&self, _r_in: &Ray, _rec: &HitRecord) -> Option<(Colour, Vec3)> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_material_default_albedo() {
+        assert!(eq(Plain.albedo(), Colour::new(0.5, 0.5, 0.5)));
+    }
+
+    #[test]
+    fn test_material_metal() {
+        let (r, rec) = floor_hit();
+        let (_, d) = Metal::new(Colour::new(1.0, 1.0, 1.0), 0.0)
+            .scatter(&r, &rec)
+            .unwrap();
+        assert!(eq(normalise(d), normalise(Vec3::new(1.0, 1.0, 0.0))));
+    }
+
+    #[test]
+    fn test_material_dielectric() {
+        // the same index on both sides, so no reflection at all and straight through
+        let (r, rec) = floor_hit();
+        let (attenuation, d) = Dielectric::new(1.0).scatter(&r, &rec).unwrap();
+        assert!(eq(attenuation, Colour::new(1.0, 1.0, 1.0)));
+        assert!(eq(d, r.direction()));
+
+        // leaving glass at a grazing angle it can't get out, so is always reflected
+        let r = Ray::new(origin(), Vec3::new(1.0, 0.1, 0.0));
+        let mut rec = HitRecord::new();
+        rec.set_face_normal(&r, unit_y());
+        assert!(!rec.front_face);
+        for _ in 0..100 {
+            let (_, d) = Dielectric::new(1.5).scatter(&r, &rec).unwrap();
+            assert!(d.y() < 0.0);
+            assert_relative_eq!(d.x(), r.direction().x());
+        }
+    }
+
+    #[test]
+    fn test_material_emissive() {
+        let (r, rec) = floor_hit();
+        let m = Emissive::new(4.0 * unit_x());
+        assert!(m.scatter(&r, &rec).is_none());
+        assert!(eq(m.albedo(), Colour::new(0.0, 0.0, 0.0)));
+        assert!(eq(m.emitted(&rec), 4.0 * unit_x()));
+    }
+}
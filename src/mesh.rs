@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+use crate::vec3::{cross, dot, Point3, Vec3};
+
+// A named scalar value at each vertex, e.g. curvature or the distance to some other surface,
+// carried through to formats that can store it
+#[derive(Clone, Default)]
+pub struct Attribute {
+    pub name: String,
+    pub values: Vec<f64>,
+}
+
+// An indexed triangle mesh, as produced by polygonizing an implicit surface. Vertices are shared
+// between the triangles that meet at them and each carries the surface normal at that point.
+#[derive(Clone, Default)]
+pub struct Mesh {
+    pub vertices: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub triangles: Vec<[usize; 3]>,
+    pub attributes: Vec<Attribute>,
+}
+
+impl Mesh {
+    pub fn new() -> Mesh {
+        Default::default()
+    }
+
+    pub fn add_vertex(&mut self, p: Point3, normal: Vec3) -> usize {
+        self.vertices.push(p);
+        self.normals.push(normal);
+        self.vertices.len() - 1
+    }
+
+    // Adds a triangle wound counter-clockwise when seen from the side its vertex normals point
+    // to, so that the facet normal faces out of the surface. Triangles that have collapsed to a
+    // line or point are dropped.
+    pub fn add_triangle(&mut self, a: usize, b: usize, c: usize) {
+        let outward = self.normals[a] + self.normals[b] + self.normals[c];
+        self.add_triangle_facing(a, b, c, outward);
+    }
+
+    // as add_triangle, but wound so the facet normal faces the given direction
+    pub fn add_triangle_facing(&mut self, a: usize, b: usize, c: usize, outward: Vec3) {
+        let n = self.facet_normal([a, b, c]);
+        if n.length_squared() == 0.0 {
+            return;
+        }
+
+        if dot(n, outward) < 0.0 {
+            self.triangles.push([a, c, b]);
+        } else {
+            self.triangles.push([a, b, c]);
+        }
+    }
+
+    // Evaluates f at every vertex, given its position and normal, and stores the result as a
+    // per-vertex attribute. This should be done once the mesh is complete.
+    pub fn add_attribute(&mut self, name: &str, f: impl Fn(Point3, Vec3) -> f64) {
+        let values = self
+            .vertices
+            .iter()
+            .zip(&self.normals)
+            .map(|(p, n)| f(*p, *n))
+            .collect();
+        self.attributes.push(Attribute {
+            name: name.to_string(),
+            values,
+        });
+    }
+
+    // Whether the mesh is closed and consistently wound, i.e. every edge is used exactly once in
+    // each direction. This is what CAD packages need to treat the mesh as a solid.
+    pub fn is_closed(&self) -> bool {
+        let mut edges: HashMap<(usize, usize), usize> = HashMap::new();
+        for t in &self.triangles {
+            for k in 0..3 {
+                *edges.entry((t[k], t[(k + 1) % 3])).or_default() += 1;
+            }
+        }
+        edges
+            .iter()
+            .all(|(&(a, b), &n)| n == 1 && edges.get(&(b, a)) == Some(&1))
+    }
+
+    // unnormalised normal of the plane of a triangle, following its winding
+    pub fn facet_normal(&self, t: [usize; 3]) -> Vec3 {
+        let [a, b, c] = t.map(|i| self.vertices[i]);
+        cross(b - a, c - a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vec3::{eq, normalise, origin, unit_x, unit_y, unit_z};
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_mesh_winding() {
+        let mut m = Mesh::new();
+        let a = m.add_vertex(origin(), unit_z());
+        let b = m.add_vertex(unit_x(), unit_z());
+        let c = m.add_vertex(unit_y(), unit_z());
+
+        // given clockwise, stored counter-clockwise
+        m.add_triangle(a, c, b);
+        assert_eq!(m.triangles, vec![[a, b, c]]);
+        assert!(eq(normalise(m.facet_normal(m.triangles[0])), unit_z()));
+
+        // degenerate triangles are dropped
+        m.add_triangle(a, b, b);
+        assert_eq!(m.triangles.len(), 1);
+    }
+
+    #[test]
+    fn test_mesh_attribute() {
+        let mut m = Mesh::new();
+        m.add_vertex(origin(), unit_z());
+        m.add_vertex(unit_x(), unit_y());
+        m.add_attribute("height", |p, n| p.x() + n.y());
+
+        assert_eq!(m.attributes[0].name, "height");
+        assert_eq!(m.attributes[0].values, vec![0.0, 2.0]);
+    }
+
+    #[test]
+    fn test_mesh_closed() {
+        // a tetrahedron
+        let mut m = Mesh::new();
+        let p = [origin(), unit_x(), unit_y(), unit_z()];
+        let centre = 0.25 * (p[0] + p[1] + p[2] + p[3]);
+        for v in p {
+            m.add_vertex(v, v - centre);
+        }
+
+        m.add_triangle(0, 1, 2);
+        m.add_triangle(0, 1, 3);
+        m.add_triangle(0, 2, 3);
+        assert!(!m.is_closed());
+
+        m.add_triangle(1, 2, 3);
+        assert!(m.is_closed());
+    }
+}
@@ -0,0 +1,48 @@
+use std::io::{self, Write};
+
+use crate::mesh::Mesh;
+
+// Wavefront OBJ keeps the shared vertices and their normals. Indices are 1-based and each face
+// corner refers to the vertex and normal with the same index. There is no standard place for
+// per-vertex scalars so any attributes on the mesh are left out; use PLY for those.
+pub fn write(out: &mut impl Write, mesh: &Mesh, name: &str) -> io::Result<()> {
+    writeln!(out, "o {}", name)?;
+    for v in &mesh.vertices {
+        writeln!(out, "v {}", v)?;
+    }
+    for n in &mesh.normals {
+        writeln!(out, "vn {}", n)?;
+    }
+    for t in &mesh.triangles {
+        let [a, b, c] = t.map(|i| i + 1);
+        writeln!(out, "f {a}//{a} {b}//{b} {c}//{c}")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vec3::{origin, unit_x, unit_y, unit_z};
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_obj() {
+        let mut m = Mesh::new();
+        let a = m.add_vertex(origin(), unit_z());
+        let b = m.add_vertex(unit_x(), unit_z());
+        let c = m.add_vertex(unit_y(), unit_z());
+        m.add_triangle(a, b, c);
+
+        let mut out = Vec::new();
+        write(&mut out, &m, "tri").unwrap();
+
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "o tri");
+        assert_eq!(lines[2], "v 1 0 0");
+        assert_eq!(lines[4], "vn 0 0 1");
+        assert_eq!(lines[7], "f 1//1 2//2 3//3");
+    }
+}
@@ -0,0 +1,209 @@
+use crate::hittable::ImplicitSurface;
+use crate::interval;
+use crate::vec3::{Point3, Vec3};
+
+enum Node {
+    // pruned, the surface cannot pass through this cell
+    Empty,
+    // a cell at the finest level that the surface may pass through
+    Leaf,
+    Branch(Box<[Node; 8]>),
+}
+
+// An adaptive subdivision of space for meshing, which only refines the cells that the surface
+// can pass through.
+//
+// A cell is pruned when the distance at its centre is more than half its diagonal: the surface
+// would have to be closer than that to reach inside. As in sphere tracing, the field is divided
+// by its Lipschitz bound over the cell to give a distance, which never overestimates the distance
+// to the surface as long as the bound holds. A bound given for an equation may be wrong, or
+// missing, so a cell is only pruned if the field's bound over it, see
+// ImplicitSurface::signed_distance_interval, also rules the surface out.
+//
+// Every cell the surface touches is refined all the way to the finest level, so the surface
+// only ever passes between cells of the same size. Meshing the leaves therefore gives the same
+// crack-free result as a uniform grid at that resolution, while the number of evaluations grows
+// with the area of the surface rather than the volume of the box.
+pub struct Octree {
+    min: Point3,
+    // edge length of the root cube
+    size: f64,
+    depth: u32,
+    root: Node,
+}
+
+impl Octree {
+    // Builds a tree over the cube with its minimum corner at min that covers the box min-max.
+    // The finest cells have 2^depth along each side.
+    pub fn new(surface: &dyn ImplicitSurface, min: Point3, max: Point3, depth: u32) -> Octree {
+        let mut tree = Octree {
+            min,
+            size: (max - min).max_component(),
+            depth,
+            root: Node::Empty,
+        };
+        tree.root = tree.build(surface, [0, 0, 0], 0);
+        tree
+    }
+
+    pub fn min(&self) -> Point3 {
+        self.min
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    // edge length of the finest cells
+    pub fn cell_size(&self) -> f64 {
+        self.size / (1u64 << self.depth) as f64
+    }
+
+    // c is the minimum corner of the cell in units of the finest cells
+    fn build(&self, surface: &dyn ImplicitSurface, c: [usize; 3], level: u32) -> Node {
+        let span = 1usize << (self.depth - level);
+        let size = span as f64 * self.cell_size();
+
+        let centre = self.min
+            + self.cell_size() * Vec3::new(c[0] as f64, c[1] as f64, c[2] as f64)
+            + Vec3::new(0.5 * size, 0.5 * size, 0.5 * size);
+
+        let half_diagonal = 0.5 * f64::sqrt(3.0) * size;
+        let distance =
+            surface.signed_distance(centre).abs() / surface.lipschitz(centre, half_diagonal);
+        let r = Vec3::new(0.5 * size, 0.5 * size, 0.5 * size);
+        if distance > half_diagonal
+            && !surface
+                .signed_distance_interval(interval::bounds(centre - r, centre + r))
+                .contains(0.0)
+        {
+            return Node::Empty;
+        }
+
+        if level == self.depth {
+            return Node::Leaf;
+        }
+
+        let half = span / 2;
+        let children = std::array::from_fn(|i| {
+            let child = [
+                c[0] + half * (i & 1),
+                c[1] + half * ((i >> 1) & 1),
+                c[2] + half * ((i >> 2) & 1),
+            ];
+            self.build(surface, child, level + 1)
+        });
+        Node::Branch(Box::new(children))
+    }
+
+    // the minimum corners of the finest cells the surface may pass through, in units of those cells
+    pub fn leaves(&self) -> Vec<[usize; 3]> {
+        let mut out = vec![];
+        collect_leaves(&self.root, [0, 0, 0], 1 << self.depth, &mut out);
+        out
+    }
+}
+
+fn collect_leaves(node: &Node, c: [usize; 3], span: usize, out: &mut Vec<[usize; 3]>) {
+    match node {
+        Node::Empty => {}
+        Node::Leaf => out.push(c),
+        Node::Branch(children) => {
+            let half = span / 2;
+            for (i, child) in children.iter().enumerate() {
+                let cc = [
+                    c[0] + half * (i & 1),
+                    c[1] + half * ((i >> 1) & 1),
+                    c[2] + half * ((i >> 2) & 1),
+                ];
+                collect_leaves(child, cc, half, out);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use crate::dual::Dual;
+    use crate::sphere::Sphere;
+    use crate::vec3::origin;
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    // counts how many times the field is evaluated
+    struct Counted {
+        inner: Sphere,
+        count: Cell<usize>,
+    }
+
+    impl ImplicitSurface for Counted {
+        fn signed_distance(&self, v: Vec3) -> f64 {
+            self.count.set(self.count.get() + 1);
+            self.inner.signed_distance(v)
+        }
+
+        fn signed_distance_dual(&self, v: [Dual; 3]) -> Dual {
+            self.inner.signed_distance_dual(v)
+        }
+
+        fn gradient(&self, v: Vec3) -> Vec3 {
+            self.inner.gradient(v)
+        }
+    }
+
+    #[test]
+    fn test_octree_leaves_hug_surface() {
+        let s = Sphere::new(origin(), 1.0);
+        let lo = Vec3::new(-2.0, -2.0, -2.0);
+        let tree = Octree::new(&s, lo, -lo, 5);
+        assert_eq!(tree.cell_size(), 4.0 / 32.0);
+
+        let leaves = tree.leaves();
+        assert!(!leaves.is_empty());
+
+        // every leaf is close to the surface
+        let h = tree.cell_size();
+        for c in &leaves {
+            let centre =
+                lo + h * Vec3::new(c[0] as f64 + 0.5, c[1] as f64 + 0.5, c[2] as f64 + 0.5);
+            assert!(s.signed_distance(centre).abs() <= h);
+        }
+
+        // and every cell the surface passes through is a leaf
+        let n = 32;
+        for x in 0..n {
+            for y in 0..n {
+                for z in 0..n {
+                    let corner = |i: usize, j: usize, k: usize| {
+                        lo + h * Vec3::new((x + i) as f64, (y + j) as f64, (z + k) as f64)
+                    };
+                    let inside = (0..8)
+                        .filter(|m| {
+                            s.signed_distance(corner(m & 1, (m >> 1) & 1, (m >> 2) & 1)) < 0.0
+                        })
+                        .count();
+                    if inside != 0 && inside != 8 {
+                        assert!(leaves.contains(&[x, y, z]));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_octree_saves_evaluations() {
+        let s = Counted {
+            inner: Sphere::new(origin(), 1.0),
+            count: Cell::new(0),
+        };
+        let lo = Vec3::new(-2.0, -2.0, -2.0);
+        let tree = Octree::new(&s, lo, -lo, 6);
+
+        // a uniform grid would need a sample at every one of the 65^3 corners
+        assert!(s.count.get() < 65 * 65 * 65 / 4);
+        assert!(tree.leaves().len() < 64 * 64 * 64 / 4);
+    }
+}
@@ -0,0 +1,57 @@
+use crate::dual::{self, Dual, Scalar};
+use crate::hittable::ImplicitSurface;
+use crate::vec3::{normalise, Point3, Vec3};
+
+// An infinite plane through p, with the normal pointing to the outside half-space
+#[derive(Copy, Clone, Default)]
+pub struct Plane {
+    p: Point3,
+    normal: Vec3,
+}
+
+impl Plane {
+    pub fn new(p: Point3, normal: Vec3) -> Plane {
+        Plane {
+            p,
+            normal: normalise(normal),
+        }
+    }
+
+    // signed distance function for a plane is the projection of v - p onto its normal
+    fn distance<T: Scalar>(&self, v: [T; 3]) -> T {
+        dual::dot(dual::offset(v, self.p), self.normal)
+    }
+}
+
+impl ImplicitSurface for Plane {
+    fn signed_distance(&self, v: Vec3) -> f64 {
+        self.distance(dual::constant(v))
+    }
+
+    fn signed_distance_dual(&self, v: [Dual; 3]) -> Dual {
+        self.distance(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vec3::{eq, unit_x, unit_y, unit_z};
+    use approx::assert_relative_eq;
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_plane_sdf() {
+        let s = Plane::new(-unit_y(), 2.0 * unit_y());
+        assert_relative_eq!(s.signed_distance(-unit_y() + 5.0 * unit_x()), 0.0);
+        assert_relative_eq!(s.signed_distance(unit_y()), 2.0);
+        assert_relative_eq!(s.signed_distance(-3.0 * unit_y() + unit_z()), -2.0);
+    }
+
+    #[test]
+    fn test_plane_gradient() {
+        let s = Plane::new(-unit_y(), 2.0 * unit_y());
+        assert!(eq(s.gradient(unit_x()), unit_y()));
+    }
+}
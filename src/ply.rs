@@ -0,0 +1,107 @@
+use std::io::{self, Write};
+
+use crate::mesh::Mesh;
+
+// PLY keeps the shared vertices and their normals, and any per-vertex attributes on the mesh
+// become extra float properties of the vertex element named after the attribute.
+
+fn write_header(out: &mut impl Write, mesh: &Mesh, format: &str) -> io::Result<()> {
+    writeln!(out, "ply")?;
+    writeln!(out, "format {} 1.0", format)?;
+    writeln!(out, "comment implicit-surface-gen")?;
+    writeln!(out, "element vertex {}", mesh.vertices.len())?;
+    for p in ["x", "y", "z", "nx", "ny", "nz"] {
+        writeln!(out, "property float {}", p)?;
+    }
+    for a in &mesh.attributes {
+        writeln!(out, "property float {}", a.name)?;
+    }
+    writeln!(out, "element face {}", mesh.triangles.len())?;
+    writeln!(out, "property list uchar int vertex_indices")?;
+    writeln!(out, "end_header")
+}
+
+// the values stored for vertex i, in the order they are declared in the header
+fn vertex_row(mesh: &Mesh, i: usize) -> Vec<f64> {
+    let (v, n) = (mesh.vertices[i], mesh.normals[i]);
+    let mut row = vec![v.x(), v.y(), v.z(), n.x(), n.y(), n.z()];
+    row.extend(mesh.attributes.iter().map(|a| a.values[i]));
+    row
+}
+
+pub fn write_ascii(out: &mut impl Write, mesh: &Mesh) -> io::Result<()> {
+    write_header(out, mesh, "ascii")?;
+    for i in 0..mesh.vertices.len() {
+        let row: Vec<String> = vertex_row(mesh, i).iter().map(|x| x.to_string()).collect();
+        writeln!(out, "{}", row.join(" "))?;
+    }
+    for [a, b, c] in &mesh.triangles {
+        writeln!(out, "3 {} {} {}", a, b, c)?;
+    }
+    Ok(())
+}
+
+pub fn write_binary(out: &mut impl Write, mesh: &Mesh) -> io::Result<()> {
+    write_header(out, mesh, "binary_little_endian")?;
+    for i in 0..mesh.vertices.len() {
+        for x in vertex_row(mesh, i) {
+            out.write_all(&(x as f32).to_le_bytes())?;
+        }
+    }
+    for t in &mesh.triangles {
+        out.write_all(&[3u8])?;
+        for i in t {
+            out.write_all(&(*i as i32).to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vec3::{origin, unit_x, unit_y, unit_z};
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    fn triangle() -> Mesh {
+        let mut m = Mesh::new();
+        let a = m.add_vertex(origin(), unit_z());
+        let b = m.add_vertex(unit_x(), unit_z());
+        let c = m.add_vertex(unit_y(), unit_z());
+        m.add_triangle(a, b, c);
+        m.add_attribute("distance", |p, _| p.x() - p.y());
+        m
+    }
+
+    #[test]
+    fn test_ply_ascii() {
+        let mut out = Vec::new();
+        write_ascii(&mut out, &triangle()).unwrap();
+
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("ply\nformat ascii 1.0\n"));
+        assert!(text.contains("element vertex 3\n"));
+        assert!(text.contains("property float distance\n"));
+        assert!(text.contains("element face 1\n"));
+        assert!(text.contains("\n1 0 0 0 0 1 1\n"));
+        assert!(text.contains("\n0 1 0 0 0 1 -1\n"));
+        assert!(text.ends_with("\n3 0 1 2\n"));
+    }
+
+    #[test]
+    fn test_ply_binary() {
+        let mut out = Vec::new();
+        write_binary(&mut out, &triangle()).unwrap();
+
+        let end = b"end_header\n";
+        let body = out.windows(end.len()).position(|w| w == end).unwrap() + end.len();
+
+        // three vertices of seven floats, then one face of a count and three indices
+        assert_eq!(out.len() - body, 3 * 7 * 4 + 1 + 3 * 4);
+
+        // the attribute of the second vertex
+        let at = body + 7 * 4 + 6 * 4;
+        assert_eq!(f32::from_le_bytes(out[at..at + 4].try_into().unwrap()), 1.0);
+    }
+}
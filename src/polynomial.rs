@@ -0,0 +1,481 @@
+use std::collections::BTreeMap;
+use std::ops::{Add, Mul, Neg, Sub};
+
+use crate::vec3::{Point3, Vec3};
+
+// Polynomials, for surfaces whose implicit function is one. Along a ray such a surface is a
+// polynomial in the ray parameter t, whose roots can be found exactly rather than marched to.
+
+// coefficients below this fraction of the largest are taken to be rounding error
+const COEFFICIENT_TOL: f64 = 1e-12;
+
+// roots are found to within this
+const ROOT_TOL: f64 = 1e-10;
+
+// The highest degree of polynomial surface we'll trace. Sturm sequences lose precision quickly as
+// the degree grows, and expanding high powers of x, y and z gives ever more terms.
+pub const MAX_DEGREE: u32 = 16;
+
+// A polynomial in one variable t, as its coefficients from the constant term up. There are no
+// trailing zeros, so the zero polynomial has no coefficients at all.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Polynomial {
+    c: Vec<f64>,
+}
+
+impl Polynomial {
+    pub fn new(mut c: Vec<f64>) -> Polynomial {
+        while c.last() == Some(&0.0) {
+            c.pop();
+        }
+        Polynomial { c }
+    }
+
+    pub fn coefficients(&self) -> &[f64] {
+        &self.c
+    }
+
+    // the zero polynomial has no degree, which we call 0 along with the constants
+    pub fn degree(&self) -> usize {
+        self.c.len().saturating_sub(1)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.c.is_empty()
+    }
+
+    pub fn eval(&self, t: f64) -> f64 {
+        self.c.iter().rev().fold(0.0, |acc, c| acc * t + c)
+    }
+
+    pub fn derivative(&self) -> Polynomial {
+        Polynomial::new(
+            self.c
+                .iter()
+                .enumerate()
+                .skip(1)
+                .map(|(i, c)| i as f64 * c)
+                .collect(),
+        )
+    }
+
+    // drop coefficients that are tiny next to the largest, e.g. left over from cancellation
+    fn trimmed(mut self, scale: f64) -> Polynomial {
+        for c in &mut self.c {
+            if c.abs() <= COEFFICIENT_TOL * scale {
+                *c = 0.0;
+            }
+        }
+        Polynomial::new(self.c)
+    }
+
+    fn largest(&self) -> f64 {
+        self.c.iter().fold(0.0, |m, c| m.max(c.abs()))
+    }
+
+    // the remainder of long division by d, which must not be zero
+    fn rem(&self, d: &Polynomial) -> Polynomial {
+        let mut r = self.c.clone();
+        let n = d.c.len();
+        let lead = d.c[n - 1];
+
+        while r.len() >= n {
+            let q = r[r.len() - 1] / lead;
+            let shift = r.len() - n;
+            for (i, c) in d.c.iter().enumerate() {
+                r[shift + i] -= q * c;
+            }
+            // cancelled exactly, whatever rounding says
+            r.pop();
+        }
+
+        Polynomial::new(r).trimmed(self.largest())
+    }
+
+    // The Sturm sequence p, p', then the negated remainder of each pair until it runs out. The
+    // number of distinct roots in (a, b] is how many more sign changes the sequence has at a than
+    // at b. Each term is scaled to keep the numbers in range, which doesn't change its signs.
+    pub fn sturm(&self) -> Vec<Polynomial> {
+        let mut seq = vec![self.clone(), self.derivative()];
+        while !seq[seq.len() - 1].is_zero() && seq[seq.len() - 1].degree() > 0 {
+            let next = -seq[seq.len() - 2].rem(&seq[seq.len() - 1]);
+            let m = next.largest();
+            seq.push(if m > 0.0 { next * (1.0 / m) } else { next });
+        }
+        seq.retain(|p| !p.is_zero());
+        seq
+    }
+
+    // No root is further from zero than this (Cauchy's bound)
+    pub fn root_bound(&self) -> f64 {
+        let lead = self.c[self.c.len() - 1].abs();
+        1.0 + self.c[..self.c.len() - 1]
+            .iter()
+            .fold(0.0, |m: f64, c| m.max(c.abs() / lead))
+    }
+
+    // The smallest root in (lo, hi], if there is one. Sturm's theorem says how many roots there
+    // are in a range, so we halve the range, keeping the nearer half whenever it has any, until it
+    // holds just the first root. Then if the polynomial changes sign across it we close in with
+    // Newton's method, falling back to bisection when Newton leaves the range. A root of even
+    // multiplicity, where the polynomial just touches zero, has no sign change so we keep halving.
+    pub fn first_root(&self, lo: f64, hi: f64) -> Option<f64> {
+        if self.degree() == 0 {
+            return None;
+        }
+
+        let seq = self.sturm();
+        let count = |t: f64| sign_changes(&seq, t);
+
+        let (mut a, mut b) = (lo, hi.min(self.root_bound()));
+        if b <= a {
+            return None;
+        }
+
+        let ca = count(a);
+        if ca == count(b) {
+            return None;
+        }
+
+        while ca - count(b) > 1 && b - a > ROOT_TOL {
+            let m = 0.5 * (a + b);
+            if count(m) < ca {
+                b = m;
+            } else {
+                a = m;
+            }
+        }
+
+        let (fa, fb) = (self.eval(a), self.eval(b));
+        if fb == 0.0 {
+            return Some(b);
+        }
+        if fa.signum() == fb.signum() {
+            while b - a > ROOT_TOL {
+                let m = 0.5 * (a + b);
+                if count(m) < ca {
+                    b = m;
+                } else {
+                    a = m;
+                }
+            }
+            return Some(b);
+        }
+
+        let df = self.derivative();
+        let mut t = 0.5 * (a + b);
+        for _ in 0..100 {
+            let f = self.eval(t);
+            if f == 0.0 {
+                break;
+            }
+            if f.signum() == fa.signum() {
+                a = t;
+            } else {
+                b = t;
+            }
+            if b - a <= ROOT_TOL {
+                break;
+            }
+
+            let newton = t - f / df.eval(t);
+            t = if a < newton && newton < b {
+                newton
+            } else {
+                0.5 * (a + b)
+            };
+        }
+        Some(t)
+    }
+}
+
+// the number of sign changes in the Sturm sequence at t, ignoring zeros
+fn sign_changes(seq: &[Polynomial], t: f64) -> usize {
+    let mut changes = 0;
+    let mut last = 0.0;
+    for p in seq {
+        let v = p.eval(t);
+        if v == 0.0 {
+            continue;
+        }
+        if last * v < 0.0 {
+            changes += 1;
+        }
+        last = v;
+    }
+    changes
+}
+
+impl Neg for Polynomial {
+    type Output = Polynomial;
+    fn neg(self) -> Polynomial {
+        self * -1.0
+    }
+}
+
+impl Add for Polynomial {
+    type Output = Polynomial;
+    fn add(self, o: Polynomial) -> Polynomial {
+        let mut c = vec![0.0; self.c.len().max(o.c.len())];
+        for (i, a) in self.c.iter().enumerate() {
+            c[i] += a;
+        }
+        for (i, b) in o.c.iter().enumerate() {
+            c[i] += b;
+        }
+        Polynomial::new(c)
+    }
+}
+
+impl Mul for Polynomial {
+    type Output = Polynomial;
+    fn mul(self, o: Polynomial) -> Polynomial {
+        if self.is_zero() || o.is_zero() {
+            return Polynomial::default();
+        }
+
+        let mut c = vec![0.0; self.c.len() + o.c.len() - 1];
+        for (i, a) in self.c.iter().enumerate() {
+            for (j, b) in o.c.iter().enumerate() {
+                c[i + j] += a * b;
+            }
+        }
+        Polynomial::new(c)
+    }
+}
+
+impl Mul<f64> for Polynomial {
+    type Output = Polynomial;
+    fn mul(self, k: f64) -> Polynomial {
+        Polynomial::new(self.c.iter().map(|c| k * c).collect())
+    }
+}
+
+// A polynomial in x, y and z, as the coefficient of each product of powers x^i y^j z^k, keyed by
+// [i, j, k]. Terms with a zero coefficient are left out.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Polynomial3 {
+    terms: BTreeMap<[u32; 3], f64>,
+}
+
+impl Polynomial3 {
+    pub fn constant(c: f64) -> Polynomial3 {
+        Polynomial3::from_terms([([0, 0, 0], c)])
+    }
+
+    // x, y or z for 0, 1 or 2
+    pub fn variable(i: usize) -> Polynomial3 {
+        let mut powers = [0; 3];
+        powers[i] = 1;
+        Polynomial3::from_terms([(powers, 1.0)])
+    }
+
+    fn from_terms(terms: impl IntoIterator<Item = ([u32; 3], f64)>) -> Polynomial3 {
+        let mut p = Polynomial3::default();
+        for (powers, c) in terms {
+            *p.terms.entry(powers).or_default() += c;
+        }
+        p.terms.retain(|_, c| *c != 0.0);
+        p
+    }
+
+    pub fn degree(&self) -> u32 {
+        self.terms.keys().map(|p| p.iter().sum()).max().unwrap_or(0)
+    }
+
+    pub fn powi(&self, n: u32) -> Polynomial3 {
+        (0..n).fold(Polynomial3::constant(1.0), |acc, _| acc * self.clone())
+    }
+
+    pub fn eval(&self, v: Point3) -> f64 {
+        self.terms
+            .iter()
+            .map(|(p, c)| {
+                c * v.x().powi(p[0] as i32) * v.y().powi(p[1] as i32) * v.z().powi(p[2] as i32)
+            })
+            .sum()
+    }
+
+    // the partial derivative with respect to x, y or z
+    pub fn derivative(&self, i: usize) -> Polynomial3 {
+        Polynomial3::from_terms(self.terms.iter().filter(|(p, _)| p[i] > 0).map(|(p, c)| {
+            let mut q = *p;
+            q[i] -= 1;
+            (q, c * p[i] as f64)
+        }))
+    }
+
+    // The polynomial in t along the ray origin + t direction, found by expanding each coordinate
+    // as a polynomial in t and multiplying out
+    pub fn along(&self, origin: Point3, direction: Vec3) -> Polynomial {
+        let o = [origin.x(), origin.y(), origin.z()];
+        let d = [direction.x(), direction.y(), direction.z()];
+
+        // powers of each coordinate, built up as needed
+        let mut powers: [Vec<Polynomial>; 3] =
+            std::array::from_fn(|_| vec![Polynomial::new(vec![1.0])]);
+
+        let mut result = Polynomial::default();
+        for (p, c) in &self.terms {
+            let mut term = Polynomial::new(vec![*c]);
+            for i in 0..3 {
+                while powers[i].len() <= p[i] as usize {
+                    let next =
+                        powers[i][powers[i].len() - 1].clone() * Polynomial::new(vec![o[i], d[i]]);
+                    powers[i].push(next);
+                }
+                term = term * powers[i][p[i] as usize].clone();
+            }
+            result = result + term;
+        }
+
+        // Terms that cancel, e.g. x^2 - 2xy + y^2 along x = y, can leave the leading coefficients
+        // tiny rather than zero, and a tiny leading coefficient puts the roots' bound far away.
+        let scale = result.largest();
+        while result
+            .c
+            .last()
+            .is_some_and(|c| c.abs() <= COEFFICIENT_TOL * scale)
+        {
+            result.c.pop();
+        }
+        result
+    }
+}
+
+impl Neg for Polynomial3 {
+    type Output = Polynomial3;
+    fn neg(self) -> Polynomial3 {
+        self * -1.0
+    }
+}
+
+impl Add for Polynomial3 {
+    type Output = Polynomial3;
+    fn add(self, o: Polynomial3) -> Polynomial3 {
+        Polynomial3::from_terms(self.terms.into_iter().chain(o.terms))
+    }
+}
+
+impl Sub for Polynomial3 {
+    type Output = Polynomial3;
+    fn sub(self, o: Polynomial3) -> Polynomial3 {
+        self + -o
+    }
+}
+
+impl Mul for Polynomial3 {
+    type Output = Polynomial3;
+    fn mul(self, o: Polynomial3) -> Polynomial3 {
+        Polynomial3::from_terms(self.terms.iter().flat_map(|(p, a)| {
+            o.terms
+                .iter()
+                .map(move |(q, b)| ([p[0] + q[0], p[1] + q[1], p[2] + q[2]], a * b))
+        }))
+    }
+}
+
+impl Mul<f64> for Polynomial3 {
+    type Output = Polynomial3;
+    fn mul(self, k: f64) -> Polynomial3 {
+        Polynomial3::from_terms(self.terms.into_iter().map(|(p, c)| (p, k * c)))
+    }
+}
+
+impl Add<f64> for Polynomial3 {
+    type Output = Polynomial3;
+    fn add(self, c: f64) -> Polynomial3 {
+        self + Polynomial3::constant(c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::vec3::origin;
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    // the polynomial with the given roots
+    fn with_roots(roots: &[f64]) -> Polynomial {
+        roots.iter().fold(Polynomial::new(vec![1.0]), |p, r| {
+            p * Polynomial::new(vec![-r, 1.0])
+        })
+    }
+
+    #[test]
+    fn test_polynomial_arithmetic() {
+        let p = with_roots(&[1.0, 2.0]);
+        assert_eq!(p.coefficients(), &[2.0, -3.0, 1.0]);
+        assert_eq!(p.derivative().coefficients(), &[-3.0, 2.0]);
+        assert_relative_eq!(p.eval(3.0), 2.0);
+        assert_eq!((p.clone() + -p).degree(), 0);
+    }
+
+    #[test]
+    fn test_polynomial_sturm_counts_roots() {
+        let p = with_roots(&[-1.0, 0.5, 2.0, 3.0]);
+        let seq = p.sturm();
+        assert_eq!(sign_changes(&seq, -10.0) - sign_changes(&seq, 10.0), 4);
+        assert_eq!(sign_changes(&seq, 0.0) - sign_changes(&seq, 2.5), 2);
+        assert_eq!(sign_changes(&seq, 3.5) - sign_changes(&seq, 10.0), 0);
+    }
+
+    #[test]
+    fn test_polynomial_first_root() {
+        let p = with_roots(&[-1.0, 0.5, 2.0, 3.0]);
+        assert_relative_eq!(p.first_root(0.0, 10.0).unwrap(), 0.5, epsilon = 1e-9);
+        assert_relative_eq!(p.first_root(0.5, 10.0).unwrap(), 2.0, epsilon = 1e-9);
+        assert_relative_eq!(
+            p.first_root(0.0, f64::INFINITY).unwrap(),
+            0.5,
+            epsilon = 1e-9
+        );
+        assert!(p.first_root(3.0, 10.0).is_none());
+
+        // close together
+        let p = with_roots(&[1.0, 1.0001, 5.0]);
+        assert_relative_eq!(p.first_root(0.0, 10.0).unwrap(), 1.0, epsilon = 1e-9);
+
+        // touching zero without crossing it, which rounding lets us place less well
+        let p = with_roots(&[2.0, 2.0]);
+        assert_relative_eq!(p.first_root(0.0, 10.0).unwrap(), 2.0, epsilon = 1e-7);
+
+        // no real roots at all
+        let p = Polynomial::new(vec![1.0, 0.0, 1.0]);
+        assert!(p.first_root(-10.0, 10.0).is_none());
+    }
+
+    #[test]
+    fn test_polynomial3_along_ray() {
+        // x^2 + y^2 + z^2 - 1
+        let f = Polynomial3::variable(0).powi(2)
+            + Polynomial3::variable(1).powi(2)
+            + Polynomial3::variable(2).powi(2)
+            + -1.0;
+        assert_eq!(f.degree(), 2);
+
+        let o = Vec3::new(-3.0, 0.5, 0.0);
+        let d = Vec3::new(1.0, 0.0, 0.0);
+        let p = f.along(o, d);
+        for t in [0.0, 1.0, 2.5] {
+            assert_relative_eq!(p.eval(t), f.eval(o + t * d));
+        }
+
+        let t = p.first_root(0.0, 10.0).unwrap();
+        assert_relative_eq!(t, 3.0 - f64::sqrt(0.75), epsilon = 1e-9);
+
+        // 2x
+        let dx = f.derivative(0);
+        assert_relative_eq!(dx.eval(Vec3::new(1.5, 7.0, 7.0)), 3.0);
+
+        // (x + 3y)^2 - 1 along a line where x + 3y doesn't change, but whose square's leading
+        // coefficient doesn't quite cancel
+        let g = (Polynomial3::variable(0) + Polynomial3::variable(1) * 3.0).powi(2) + -1.0;
+        let p = g.along(origin(), Vec3::new(0.3, -0.1, 0.0));
+        assert_eq!(p.degree(), 0);
+        assert!(p.first_root(0.0, 10.0).is_none());
+    }
+}
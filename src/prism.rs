@@ -0,0 +1,127 @@
+use std::f64::consts::PI;
+
+use crate::dual::{self, Dual};
+use crate::hittable::ImplicitSurface;
+use crate::profile::{self, Point2};
+use crate::vec3::{Point3, Vec3};
+
+// A prism whose cross-section is a regular polygon in the xy-plane, extruded along z
+#[derive(Clone, Default)]
+pub struct Prism {
+    center: Point3,
+    half_height: f64,
+    // polygon vertices counter-clockwise, with the first repeated at the end to close it
+    boundary: Vec<Point2>,
+}
+
+impl Prism {
+    // a prism with the given number of sides, where radius is the distance from the axis to each
+    // vertex and the first vertex lies on the positive y axis
+    pub fn new(center: Point3, sides: usize, radius: f64, half_height: f64) -> Prism {
+        let boundary = (0..=sides)
+            .map(|i| {
+                let theta = 0.5 * PI + 2.0 * PI * (i % sides) as f64 / sides as f64;
+                [radius * theta.cos(), radius * theta.sin()]
+            })
+            .collect();
+
+        Prism {
+            center,
+            half_height,
+            boundary,
+        }
+    }
+
+    pub fn triangular(center: Point3, radius: f64, half_height: f64) -> Prism {
+        Prism::new(center, 3, radius, half_height)
+    }
+
+    pub fn hexagonal(center: Point3, radius: f64, half_height: f64) -> Prism {
+        Prism::new(center, 6, radius, half_height)
+    }
+
+    fn distance_and_gradient(&self, v: Vec3) -> (f64, Vec3) {
+        let p = v - self.center;
+
+        let region = &self.boundary[..self.boundary.len() - 1];
+        let (d, g) = profile::signed_distance([p.x(), p.y()], &self.boundary, region);
+
+        let dz = p.z().abs() - self.half_height;
+        let gz = Vec3::new(0.0, 0.0, if p.z() < 0.0 { -1.0 } else { 1.0 });
+
+        profile::extrude(d, Vec3::new(g[0], g[1], 0.0), dz, gz)
+    }
+}
+
+impl ImplicitSurface for Prism {
+    // the polygon's 2D distance combined exactly with the distance to the end caps
+    fn signed_distance(&self, v: Vec3) -> f64 {
+        self.distance_and_gradient(v).0
+    }
+
+    fn gradient(&self, v: Vec3) -> Vec3 {
+        self.distance_and_gradient(v).1
+    }
+
+    fn signed_distance_dual(&self, v: [Dual; 3]) -> Dual {
+        let (d, g) = self.distance_and_gradient(dual::value(v));
+        dual::chain(v, d, g)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vec3::{eq, normalise, origin, unit_x, unit_y, unit_z};
+    use approx::assert_relative_eq;
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_hex_prism_sdf_coi() {
+        let p = Prism::hexagonal(origin(), 1.0, 1.0);
+        let apothem = 0.75_f64.sqrt();
+        assert_relative_eq!(p.signed_distance(unit_y()), 0.0, epsilon = 1e-12);
+        assert_relative_eq!(p.signed_distance(apothem * unit_x()), 0.0, epsilon = 1e-12);
+        assert_relative_eq!(p.signed_distance(unit_z()), 0.0);
+    }
+
+    #[test]
+    fn test_hex_prism_sdf_outside_inside() {
+        let p = Prism::hexagonal(origin(), 1.0, 2.0);
+        let apothem = 0.75_f64.sqrt();
+        assert_relative_eq!(p.signed_distance(origin()), -apothem, epsilon = 1e-12);
+        assert_relative_eq!(p.signed_distance(3.0 * unit_y()), 2.0, epsilon = 1e-12);
+        assert_relative_eq!(p.signed_distance(4.0 * unit_z()), 2.0);
+
+        // off the end of a vertical edge the distance is to the corner
+        assert_relative_eq!(
+            p.signed_distance(2.0 * unit_y() + 3.0 * unit_z()),
+            2.0_f64.sqrt(),
+            epsilon = 1e-12
+        );
+    }
+
+    #[test]
+    fn test_hex_prism_gradient() {
+        let p = Prism::hexagonal(origin(), 1.0, 2.0);
+        assert!(eq(p.gradient(2.0 * unit_x()), unit_x()));
+        assert!(eq(p.gradient(0.1 * unit_x() + 1.9 * unit_z()), unit_z()));
+        assert!(eq(
+            p.gradient(2.0 * unit_y() + 3.0 * unit_z()),
+            normalise(unit_y() + unit_z())
+        ));
+    }
+
+    #[test]
+    fn test_tri_prism_sdf() {
+        let p = Prism::triangular(origin(), 2.0, 1.0);
+
+        // the inradius of an equilateral triangle is half its circumradius
+        assert_relative_eq!(p.signed_distance(origin()), -1.0, epsilon = 1e-12);
+        assert_relative_eq!(p.signed_distance(-unit_y()), 0.0, epsilon = 1e-12);
+        assert_relative_eq!(p.signed_distance(2.0 * unit_y()), 0.0, epsilon = 1e-12);
+        assert_relative_eq!(p.signed_distance(-3.0 * unit_y()), 2.0, epsilon = 1e-12);
+        assert!(eq(p.gradient(-3.0 * unit_y()), -unit_y()));
+    }
+}
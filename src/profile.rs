@@ -0,0 +1,130 @@
+use crate::common::clamp;
+use crate::vec3::Vec3;
+
+// Helpers for primitives whose surface is described by a 2D cross-section that is either
+// revolved around an axis (cones) or extruded along one (prisms). Points in the profile plane
+// are plain [x, y] pairs.
+
+pub type Point2 = [f64; 2];
+
+fn sub(u: Point2, v: Point2) -> Point2 {
+    [u[0] - v[0], u[1] - v[1]]
+}
+
+fn dot(u: Point2, v: Point2) -> f64 {
+    u[0] * v[0] + u[1] * v[1]
+}
+
+fn length(u: Point2) -> f64 {
+    f64::sqrt(dot(u, u))
+}
+
+// whether q lies inside the convex polygon whose vertices are given counter-clockwise
+fn inside_convex(q: Point2, region: &[Point2]) -> bool {
+    (0..region.len()).all(|i| {
+        let a = region[i];
+        let b = region[(i + 1) % region.len()];
+        let ab = sub(b, a);
+        let aq = sub(q, a);
+        ab[0] * aq[1] - ab[1] * aq[0] >= 0.0
+    })
+}
+
+// Exact signed distance from q to the boundary of a convex region, along with its gradient.
+//
+// The boundary is an open polyline running counter-clockwise around the region. It need not
+// close the region: a revolved profile leaves out the edge lying on the axis of revolution since
+// that edge is not part of the surface.
+pub fn signed_distance(q: Point2, boundary: &[Point2], region: &[Point2]) -> (f64, Point2) {
+    let mut best = f64::INFINITY;
+    let mut closest = q;
+    let mut edge_normal = [0.0, 0.0];
+
+    for w in boundary.windows(2) {
+        let ab = sub(w[1], w[0]);
+        let len2 = dot(ab, ab);
+        if len2 == 0.0 {
+            continue;
+        }
+
+        let h = clamp(dot(sub(q, w[0]), ab) / len2, 0.0, 1.0);
+        let c = [w[0][0] + h * ab[0], w[0][1] + h * ab[1]];
+        let d = length(sub(q, c));
+        if d < best {
+            best = d;
+            closest = c;
+            let len = f64::sqrt(len2);
+            edge_normal = [ab[1] / len, -ab[0] / len];
+        }
+    }
+
+    let sign = if inside_convex(q, region) { -1.0 } else { 1.0 };
+
+    // on the boundary itself the direction to the closest point is undefined, so fall back to
+    // the outward normal of the edge we are sitting on
+    let gradient = if best > 0.0 {
+        let qc = sub(q, closest);
+        [sign * qc[0] / best, sign * qc[1] / best]
+    } else {
+        edge_normal
+    };
+
+    (sign * best, gradient)
+}
+
+// Exact signed distance of a 2D region extruded along an axis, where d is the distance to the
+// extruded side walls and dz the distance to the pair of end planes, each with its 3D gradient.
+pub fn extrude(d: f64, g: Vec3, dz: f64, gz: Vec3) -> (f64, Vec3) {
+    if d > 0.0 || dz > 0.0 {
+        let wd = f64::max(d, 0.0);
+        let wz = f64::max(dz, 0.0);
+        let len = f64::sqrt(wd * wd + wz * wz);
+        return (len, (wd / len) * g + (wz / len) * gz);
+    }
+
+    if d > dz {
+        (d, g)
+    } else {
+        (dz, gz)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    // unit square, counter-clockwise, closed
+    const SQUARE: [Point2; 4] = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+
+    #[test]
+    fn test_profile_square() {
+        let mut boundary = SQUARE.to_vec();
+        boundary.push(SQUARE[0]);
+
+        let (d, g) = signed_distance([0.5, 0.25], &boundary, &SQUARE);
+        assert_relative_eq!(d, -0.25);
+        assert_relative_eq!(g[0], 0.0);
+        assert_relative_eq!(g[1], -1.0);
+
+        let (d, g) = signed_distance([2.0, 0.5], &boundary, &SQUARE);
+        assert_relative_eq!(d, 1.0);
+        assert_relative_eq!(g[0], 1.0);
+        assert_relative_eq!(g[1], 0.0);
+
+        // on an edge we take the edge normal
+        let (d, g) = signed_distance([0.5, 1.0], &boundary, &SQUARE);
+        assert_relative_eq!(d, 0.0);
+        assert_relative_eq!(g[0], 0.0);
+        assert_relative_eq!(g[1], 1.0);
+    }
+
+    #[test]
+    fn test_profile_open_boundary() {
+        // leave out the left hand edge, as though the square were revolved around x = 0
+        let (d, _) = signed_distance([0.1, 0.5], &SQUARE, &SQUARE);
+        assert_relative_eq!(d, -0.5);
+    }
+}
@@ -0,0 +1,171 @@
+use crate::vec3::{dot, Point3, Vec3};
+
+// Eigenvalues of A^T A below this are treated as zero when solving. With unit normals an
+// eigenvalue counts roughly how many of the planes constrain that direction, so this drops
+// directions only constrained by nearly parallel planes.
+const TRUNCATION: f64 = 0.1;
+
+// A quadratic error function: the sum of squared distances from x to a set of planes, each given
+// by a point and a unit normal. In dual contouring these are the tangent planes where the surface
+// crosses the edges of a cell, and minimising the error places the cell's vertex on a corner or
+// crease where they meet.
+#[derive(Clone, Default)]
+pub struct Qef {
+    ata: [[f64; 3]; 3],
+    atb: Vec3,
+    mass: Vec3,
+    count: usize,
+}
+
+impl Qef {
+    pub fn new() -> Qef {
+        Default::default()
+    }
+
+    pub fn add(&mut self, p: Point3, n: Vec3) {
+        let n = [n.x(), n.y(), n.z()];
+        for (i, row) in self.ata.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x += n[i] * n[j];
+            }
+        }
+
+        let n = Vec3::new(n[0], n[1], n[2]);
+        self.atb += dot(n, p) * n;
+        self.mass += p;
+        self.count += 1;
+    }
+
+    // the average of the plane points, which the solution is pulled towards along any direction
+    // the planes don't constrain
+    pub fn mass_point(&self) -> Point3 {
+        self.mass / self.count.max(1) as f64
+    }
+
+    // Minimise the error with a truncated pseudo-inverse of A^T A, solving relative to the mass
+    // point so a flat or ruled region puts the vertex in the middle of its crossings rather than
+    // wherever the null space happens to reach.
+    pub fn solve(&self) -> Point3 {
+        let c = self.mass_point();
+        let rhs = self.atb - mat_vec(self.ata, c);
+
+        let (values, vectors) = symmetric_eigen(self.ata);
+
+        let mut x = c;
+        for (i, &lambda) in values.iter().enumerate() {
+            if lambda > TRUNCATION {
+                let v = Vec3::new(vectors[0][i], vectors[1][i], vectors[2][i]);
+                x += (dot(v, rhs) / lambda) * v;
+            }
+        }
+        x
+    }
+
+    pub fn error(&self, x: Point3) -> f64 {
+        // x^T A^T A x - 2 x^T A^T b + b^T b, less the constant b^T b we don't track
+        dot(x, mat_vec(self.ata, x)) - 2.0 * dot(x, self.atb)
+    }
+}
+
+fn mat_vec(m: [[f64; 3]; 3], v: Vec3) -> Vec3 {
+    let row = |r: [f64; 3]| r[0] * v.x() + r[1] * v.y() + r[2] * v.z();
+    Vec3::new(row(m[0]), row(m[1]), row(m[2]))
+}
+
+// Eigen-decomposition of a symmetric 3x3 matrix by Jacobi rotations. Returns the eigenvalues and
+// a matrix with the corresponding eigenvectors as its columns.
+fn symmetric_eigen(mut a: [[f64; 3]; 3]) -> ([f64; 3], [[f64; 3]; 3]) {
+    let mut v = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+    for _ in 0..32 {
+        let off = a[0][1].powi(2) + a[0][2].powi(2) + a[1][2].powi(2);
+        if off < 1e-24 {
+            break;
+        }
+
+        for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+            if a[p][q] == 0.0 {
+                continue;
+            }
+
+            // rotate in the p-q plane by the angle that zeroes a[p][q]
+            let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+            let t = theta.signum() / (theta.abs() + f64::sqrt(theta * theta + 1.0));
+            let c = 1.0 / f64::sqrt(t * t + 1.0);
+            let s = t * c;
+
+            for m in [&mut a, &mut v] {
+                for row in m.iter_mut() {
+                    let (kp, kq) = (row[p], row[q]);
+                    row[p] = c * kp - s * kq;
+                    row[q] = s * kp + c * kq;
+                }
+            }
+            let (row_p, row_q) = (a[p], a[q]);
+            for (k, (pk, qk)) in row_p.iter().zip(row_q).enumerate() {
+                a[p][k] = c * pk - s * qk;
+                a[q][k] = s * pk + c * qk;
+            }
+        }
+    }
+
+    ([a[0][0], a[1][1], a[2][2]], v)
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::vec3::{eq, normalise, unit_x, unit_y, unit_z};
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_symmetric_eigen() {
+        let a = [[2.0, 1.0, 0.0], [1.0, 2.0, 0.0], [0.0, 0.0, 5.0]];
+        let (values, vectors) = symmetric_eigen(a);
+
+        for i in 0..3 {
+            let v = Vec3::new(vectors[0][i], vectors[1][i], vectors[2][i]);
+            assert!(eq(mat_vec(a, v), values[i] * v));
+        }
+
+        let mut sorted = values;
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        assert_relative_eq!(sorted[0], 1.0, epsilon = 1e-9);
+        assert_relative_eq!(sorted[1], 3.0, epsilon = 1e-9);
+        assert_relative_eq!(sorted[2], 5.0, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_qef_corner() {
+        // three faces of a box meeting at (1, 2, 3)
+        let mut q = Qef::new();
+        q.add(Vec3::new(1.0, 0.0, 0.0), unit_x());
+        q.add(Vec3::new(0.5, 2.0, 0.5), unit_y());
+        q.add(Vec3::new(0.0, 1.0, 3.0), unit_z());
+
+        let x = q.solve();
+        assert!(eq(x, Vec3::new(1.0, 2.0, 3.0)));
+    }
+
+    #[test]
+    fn test_qef_crease_stays_near_mass_point() {
+        // two planes meeting along the line x = 1, y = 0; along the crease we stay level with the
+        // mass point
+        let mut q = Qef::new();
+        q.add(
+            Vec3::new(0.0, 1.0, 0.2),
+            normalise(Vec3::new(1.0, 1.0, 0.0)),
+        );
+        q.add(
+            Vec3::new(1.0, 0.0, 0.4),
+            normalise(Vec3::new(1.0, -1.0, 0.0)),
+        );
+
+        let x = q.solve();
+        assert!(eq(x, Vec3::new(1.0, 0.0, 0.3)));
+        assert!(q.error(x) <= q.error(q.mass_point()) + 1e-12);
+    }
+}
@@ -1,9 +1,85 @@
+use serde_derive::Deserialize;
+
 use crate::{
-    common::scalar_zero,
+    dual::{self, Dual},
     hittable::{HitRecord, ImplicitSurface},
-    vec3::{normalise, Point3, Vec3},
+    interval::Interval,
+    vec3::{self, dot, normalise, Point3, Vec3},
 };
 
+// ray parameters beyond this are taken to be at infinity by the interval root finder, which has
+// to be able to split the range it searches
+const T_FAR: f64 = 1.0e6;
+
+// Pieces of ray the interval root finder may look at before giving up. Where the field is close
+// to zero for a long way without crossing it, e.g. grazing a surface, the bounds only rule out
+// very short pieces.
+const MAX_PIECES: usize = 100_000;
+
+// steps refining a root bracketed by a sign change may take, which bisection alone needs about 50
+// of to narrow any bracket to nothing
+const MAX_REFINE_STEPS: usize = 64;
+
+// how to find where a ray meets an implicit surface
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RootFinder {
+    // fast, but can step over thin features when the field isn't a distance
+    #[default]
+    SphereTracing,
+    // slower, but never misses the first root
+    Interval,
+}
+
+// How rays are marched against implicit surfaces, the [marching] section of the config
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct MarchSettings {
+    pub method: RootFinder,
+    // a ray hits once the distance is below epsilon + epsilon_per_unit * t, so with the latter
+    // set, e.g. to the angle a pixel covers, far away surfaces are hit as soon as they're within
+    // a pixel rather than marched to exactly
+    pub epsilon: f64,
+    pub epsilon_per_unit: f64,
+    // steps sphere tracing may take before giving up
+    pub max_steps: usize,
+    // sphere tracing never steps less than this, so rays grazing a surface don't crawl along it
+    // at the cost of maybe stepping into it, and no step is ever nothing
+    pub min_step: f64,
+    // Over-relaxation (Keinert et al. 2014), stepping this many times the distance. Anywhere
+    // between 1 and 2, where 1 is plain sphere tracing. If a step turns out to have been too far
+    // we go back and carry on without it.
+    pub relaxation: f64,
+}
+
+impl Default for MarchSettings {
+    fn default() -> MarchSettings {
+        MarchSettings {
+            method: RootFinder::default(),
+            epsilon: 1e-8,
+            epsilon_per_unit: 0.0,
+            max_steps: 200,
+            min_step: 1e-9,
+            relaxation: 1.0,
+        }
+    }
+}
+
+impl MarchSettings {
+    // how close counts as a hit at t along the ray
+    pub fn tolerance(&self, t: f64) -> f64 {
+        self.epsilon + self.epsilon_per_unit * t.abs()
+    }
+
+    // How far along the normal to start a ray leaving a hit at p, t along the ray that found it.
+    // The hit may be up to the tolerance it was found to from the surface, on either side, so
+    // twice that leaves the new ray at least the tolerance clear of it, plus what rounding
+    // there is in p.
+    pub fn offset(&self, t: f64, p: Point3) -> f64 {
+        2.0 * self.tolerance(t) + 16.0 * f64::EPSILON * vec3::abs(p).max_component()
+    }
+}
+
 #[derive(Default)]
 pub struct Ray {
     origin: Point3,
@@ -30,54 +106,306 @@ impl Ray {
         self.origin + (t * self.direction)
     }
 
-    pub fn trace(&self, su: &dyn ImplicitSurface, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let mut t = t_min;
-        let mut dist = su.signed_distance(self.at(t));
+    // the box holding the part of the ray between the ends of t
+    pub fn at_interval(&self, t: Interval) -> [Interval; 3] {
+        dual::offset(dual::along(t, self.direction), -self.origin)
+    }
+
+    pub fn intersect(
+        &self,
+        su: &dyn ImplicitSurface,
+        t_min: f64,
+        t_max: f64,
+        march: &MarchSettings,
+    ) -> Trace {
+        match march.method {
+            RootFinder::SphereTracing => self.trace(su, t_min, t_max, march),
+            RootFinder::Interval => self.trace_interval(su, t_min, t_max, march),
+        }
+    }
+
+    fn hit_at(&self, su: &dyn ImplicitSurface, t: f64) -> HitRecord {
+        let mut rec = HitRecord::new();
+        rec.t = t;
+        rec.p = self.at(t);
+
+        rec.normal = normalise(su.gradient(rec.p));
+        rec.set_face_normal(self, rec.normal);
+        rec
+    }
 
-        // if the ray is being fired from the surface we assume no hit since it will be an ray from a reflection
-        if scalar_zero(dist) {
-            return None;
+    // Sphere tracing (Hart 1996). If the field changes by at most L per unit length around v then
+    // no surface is nearer than |f(v)| / L, so we can step that far along the ray, from outside
+    // or inside alike; stepping out through the surface from inside is a sign change like any
+    // other. For a true distance L is one; other functions tell us their bound through
+    // ImplicitSurface::lipschitz. A bound over a ball only vouches for steps within it, so we
+    // first guess how far we could go from the bound at v alone, then take the bound over that
+    // ball and step no further than it.
+    pub fn trace(
+        &self,
+        su: &dyn ImplicitSurface,
+        t_min: f64,
+        t_max: f64,
+        march: &MarchSettings,
+    ) -> Trace {
+        let (mut t, mut dist, leaving) = self.leave_surface(su, t_min, march);
+        if dist.abs() < march.tolerance(t) {
+            return Trace::failed(Outcome::StartedOnSurface, leaving, dist);
         }
+        let mut v = self.at(t);
 
-        let mut iteration = 200;
-        while iteration > 0 {
-            t += dist;
+        let mut relaxation = march.relaxation;
+        // the last step, and the bound it was relaxed from
+        let (mut last_step, mut last_bound) = (0.0, 0.0);
 
-            let v = self.at(t);
+        for steps in leaving + 1..=march.max_steps {
+            let reach = dist.abs() / su.lipschitz(v, 0.0);
+            let lipschitz = su.lipschitz(v, reach);
+            let bound = reach.min(dist.abs() / lipschitz);
+
+            // If the spheres known to be empty around this point and the last don't overlap, the
+            // relaxed step may have jumped a gap holding some surface. Go back to where the last
+            // step would have ended unrelaxed and march plainly from there on.
+            if relaxation > 1.0 && bound + last_bound < last_step {
+                t -= last_step - last_bound;
+                relaxation = 1.0;
+                last_step = 0.0;
+                v = self.at(t);
+                dist = su.signed_distance(v);
+                continue;
+            }
+
+            last_step = (relaxation * bound).max(march.min_step);
+            last_bound = bound;
+            t += last_step;
+
+            v = self.at(t);
             let d = su.signed_distance(v);
 
-            // we've stepped outside of the maximum parameter for the ray
+            // We've stepped through the surface, as relaxed, minimum or Lipschitz steps can, and
+            // landing within tolerance would only put the hit somewhere near it. There's a root
+            // between here and the last point, so find it.
+            if d * dist < 0.0 {
+                let (t, d, refined) = self.refine(su, t - last_step, dist, t, d, march);
+                return if t > t_max {
+                    Trace::failed(Outcome::Miss, steps + refined, d)
+                } else {
+                    Trace::hit(self.hit_at(su, t), steps + refined, d)
+                };
+            }
+
+            // We've stepped outside of the maximum parameter for the ray. A relaxed step may have
+            // jumped over some surface before it, so go back as for a gap, and only miss for
+            // plain steps.
             if t > t_max {
-                return None;
+                if relaxation > 1.0 {
+                    t -= last_step - last_bound;
+                    relaxation = 1.0;
+                    last_step = 0.0;
+                    v = self.at(t);
+                    dist = su.signed_distance(v);
+                    continue;
+                }
+                return Trace::failed(Outcome::Miss, steps, d);
             }
 
-            // if we've stepped and the distance has increased, something has gone wrong so we'll just bail for now
-            if d > dist {
-                return None;
+            // if we've stepped and the distance has increased, something has gone wrong so we'll just bail for now.
+            // Fields that aren't distances can grow on the way to the surface, and relaxed steps
+            // can overshoot, so only for plain steps with a bound of one. From inside the
+            // surface can be further away after a step, e.g. heading for the far side of a ball.
+            if dist > 0.0 && relaxation == 1.0 && lipschitz == 1.0 && d > dist {
+                return Trace::failed(Outcome::Diverged, steps, d);
             }
 
             dist = d;
 
-            if scalar_zero(d) {
-                let mut rec = HitRecord::new();
-                rec.t = t;
-                rec.p = v;
+            if dist.abs() < march.tolerance(t) {
+                return Trace::hit(self.hit_at(su, t), steps, d);
+            }
+        }
 
-                rec.normal = normalise(su.gradient(v));
-                rec.set_face_normal(self, rec.normal);
-                return Some(rec);
+        Trace::failed(Outcome::Exhausted, march.max_steps, dist)
+    }
+
+    // A ray starting within the tolerance of a surface, e.g. a secondary ray that wasn't offset
+    // far enough from the hit it leaves, would find that surface straight away. It's leaving it
+    // rather than hitting it, so step along the ray until clear of it, doubling each step so a
+    // ray grazing the surface gets clear quickly, and look for hits from there. Returns where
+    // that is, the field there and the steps it took; still within the tolerance means it gave
+    // up.
+    fn leave_surface(
+        &self,
+        su: &dyn ImplicitSurface,
+        t_min: f64,
+        march: &MarchSettings,
+    ) -> (f64, f64, usize) {
+        let mut t = t_min;
+        let mut d = su.signed_distance(self.at(t));
+        let mut step = march.tolerance(t);
+
+        let mut steps = 0;
+        while d.abs() < march.tolerance(t) && steps < march.max_steps {
+            t += step;
+            step *= 2.0;
+            d = su.signed_distance(self.at(t));
+            steps += 1;
+        }
+
+        (t, d, steps)
+    }
+
+    // Find the root between a and b, where the field has opposite signs fa and fb. Newton's
+    // method on the field along the ray, whose derivative is the gradient along the direction,
+    // converges quickly near the root; any step it takes out of the bracket is replaced by
+    // bisection, so it can't wander off. Returns where it stopped, the field there and the number
+    // of steps it took.
+    fn refine(
+        &self,
+        su: &dyn ImplicitSurface,
+        a: f64,
+        fa: f64,
+        b: f64,
+        fb: f64,
+        march: &MarchSettings,
+    ) -> (f64, f64, usize) {
+        let (mut lo, mut hi) = if a < b { (a, b) } else { (b, a) };
+        let lo_sign = if a < b { fa.signum() } else { fb.signum() };
+
+        // start from the end nearer the surface
+        let (mut t, mut f) = if fa.abs() < fb.abs() {
+            (a, fa)
+        } else {
+            (b, fb)
+        };
+
+        for steps in 1..=MAX_REFINE_STEPS {
+            let d = su.signed_distance_dual(Dual::variables(self.at(t)));
+            let slope = dot(d.grad, self.direction);
+
+            let newton = t - d.value / slope;
+            t = if lo < newton && newton < hi {
+                newton
+            } else {
+                0.5 * (lo + hi)
+            };
+            f = su.signed_distance(self.at(t));
+
+            if f.abs() < march.tolerance(t) || hi - lo < march.tolerance(t) {
+                return (t, f, steps);
+            }
+
+            if f.signum() == lo_sign {
+                lo = t;
+            } else {
+                hi = t;
+            }
+        }
+
+        (t, f, MAX_REFINE_STEPS)
+    }
+
+    // Interval root finding (Mitchell 1990, Kalra and Barr 1989). Bound the surface over the
+    // part of the ray in [t_min, t_max]; if the bound doesn't hold zero there's no root in it,
+    // otherwise split it in two and look in the nearer half first. A piece of ray too short to
+    // split that can't be ruled out is the first root. Unlike sphere tracing this needs nothing
+    // of the field but that its interval bound is sound, so it can't step over a thin feature,
+    // though it may give up on rays that graze one. Each piece looked at counts as a step.
+    pub fn trace_interval(
+        &self,
+        su: &dyn ImplicitSurface,
+        t_min: f64,
+        t_max: f64,
+        march: &MarchSettings,
+    ) -> Trace {
+        // as for sphere tracing, a ray starting on a surface is leaving it
+        let (t_min, start, leaving) = self.leave_surface(su, t_min, march);
+        if start.abs() < march.tolerance(t_min) {
+            return Trace::failed(Outcome::StartedOnSurface, leaving, start);
+        }
+
+        let mut stack = vec![Interval::new(t_min, t_max.min(T_FAR))];
+
+        let mut pieces = leaving;
+        while let Some(t) = stack.pop() {
+            pieces += 1;
+            if pieces > MAX_PIECES {
+                let d = su.signed_distance(self.at(t.mid()));
+                return Trace::failed(Outcome::Exhausted, MAX_PIECES, d);
+            }
+
+            if !su
+                .signed_distance_interval(self.at_interval(t))
+                .contains(0.0)
+            {
+                continue;
             }
 
-            iteration -= 1;
+            if t.width() < march.tolerance(t.lo) {
+                let rec = self.hit_at(su, t.mid());
+                let d = su.signed_distance(rec.p);
+                return Trace::hit(rec, pieces, d);
+            }
+
+            let (near, far) = t.split();
+            stack.push(far);
+            stack.push(near);
         }
 
-        None
+        let end = su.signed_distance(self.at(t_max.min(T_FAR)));
+        Trace::failed(Outcome::Miss, pieces, end)
+    }
+}
+
+// how a trace ended
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Hit,
+    // nothing before t_max
+    Miss,
+    // gave up before finding a hit or passing t_max
+    Exhausted,
+    // the distance grew when it shouldn't have
+    Diverged,
+    // the ray started on a surface and never got clear of it, e.g. running along it
+    StartedOnSurface,
+}
+
+// The result of tracing a ray against an implicit surface: how it ended, the hit if there was
+// one, how many steps it took and the field's value where it stopped
+pub struct Trace {
+    pub outcome: Outcome,
+    pub record: Option<HitRecord>,
+    pub steps: usize,
+    pub distance: f64,
+}
+
+impl Trace {
+    fn hit(rec: HitRecord, steps: usize, distance: f64) -> Trace {
+        Trace {
+            outcome: Outcome::Hit,
+            record: Some(rec),
+            steps,
+            distance,
+        }
+    }
+
+    fn failed(outcome: Outcome, steps: usize, distance: f64) -> Trace {
+        Trace {
+            outcome,
+            record: None,
+            steps,
+            distance,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{sphere::Sphere, vec3::origin, vec3::unit_x};
+    use std::collections::HashMap;
+
+    use crate::{
+        equation::Equation, plane::Plane, sphere::Sphere, vec3::origin, vec3::unit_x, vec3::unit_y,
+    };
     use approx::assert_relative_eq;
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
@@ -132,7 +460,10 @@ mod tests {
 
         //let boxed: Box<dyn ImplicitSurface> = Box::new(sphere);
 
-        let rec = r.trace(&sphere, 0 as f64, 10 as f64).unwrap();
+        let rec = r
+            .trace(&sphere, 0.0, 10.0, &MarchSettings::default())
+            .record
+            .unwrap();
 
         assert_relative_eq!(rec.p.x(), 4.0);
         assert_relative_eq!(rec.p.y(), 0.0);
@@ -142,4 +473,245 @@ mod tests {
         assert_relative_eq!(rec.normal.y(), 0.0);
         assert_relative_eq!(rec.normal.z(), 0.0);
     }
+
+    #[test]
+    fn test_ray_lipschitz_global() {
+        // the same sphere as the square of the distance to its centre, less 1, whose gradient is
+        // at most 10 along the ray
+        let f = Equation::parse("(x - 5)^2 + y^2 + z^2 - 1", &HashMap::new()).unwrap();
+        let r = Ray::new(origin(), unit_x());
+
+        // stepping by the value of f, 24, jumps straight through it
+        assert!(r
+            .trace(&f, 0.0, 10.0, &MarchSettings::default())
+            .record
+            .is_none());
+
+        let rec = r
+            .trace(
+                &f.with_lipschitz(10.0),
+                0.0,
+                10.0,
+                &MarchSettings::default(),
+            )
+            .record
+            .unwrap();
+        assert_relative_eq!(rec.p.x(), 4.0, epsilon = 1e-8);
+        assert_relative_eq!(rec.normal.x(), -1.0);
+    }
+
+    #[test]
+    fn test_ray_lipschitz_region() {
+        // as above but giving the gradient bound, 2 |v - c|, around each point
+        struct Squared;
+        impl ImplicitSurface for Squared {
+            fn signed_distance(&self, v: Vec3) -> f64 {
+                (v - 5.0 * unit_x()).length_squared() - 1.0
+            }
+
+            fn lipschitz(&self, v: Vec3, radius: f64) -> f64 {
+                2.0 * ((v - 5.0 * unit_x()).length() + radius)
+            }
+        }
+
+        // from far away along the ray, where no global bound would do
+        let r = Ray::new(-1000.0 * unit_x(), unit_x());
+        let rec = r
+            .trace(&Squared, 0.0, 2000.0, &MarchSettings::default())
+            .record
+            .unwrap();
+        assert_relative_eq!(rec.p.x(), 4.0, epsilon = 1e-8);
+    }
+
+    #[test]
+    fn test_ray_interval() {
+        // surfaces that are distances are bounded through their Lipschitz bound
+        let r = Ray::new(origin(), unit_x());
+        let rec = r
+            .trace_interval(
+                &Sphere::new(5.0 * unit_x(), 1.0),
+                0.0,
+                f64::INFINITY,
+                &MarchSettings::default(),
+            )
+            .record
+            .unwrap();
+        assert_relative_eq!(rec.p.x(), 4.0, epsilon = 1e-8);
+        assert_relative_eq!(rec.normal.x(), -1.0);
+
+        // and those that aren't are evaluated on intervals, so need no bound to be found
+        let f = Equation::parse("(x - 5)^2 + y^2 + z^2 - 1", &HashMap::new()).unwrap();
+        let interval = MarchSettings {
+            method: RootFinder::Interval,
+            ..Default::default()
+        };
+        let rec = r.intersect(&f, 0.0, 10.0, &interval).record.unwrap();
+        assert_relative_eq!(rec.p.x(), 4.0, epsilon = 1e-8);
+
+        assert!(r
+            .trace_interval(&f, 0.0, 3.0, &MarchSettings::default())
+            .record
+            .is_none());
+    }
+
+    #[test]
+    fn test_ray_interval_first_root() {
+        // Steiner's Roman surface, which sphere tracing can't follow, crossed several times
+        let f = Equation::parse(
+            "x^2 * y^2 + y^2 * z^2 + z^2 * x^2 - x * y * z",
+            &HashMap::new(),
+        )
+        .unwrap();
+        let r = Ray::new(Vec3::new(-1.0, -0.6, -0.8), Vec3::new(1.0, 0.7, 0.9));
+        let rec = r
+            .trace_interval(&f, 0.0, 4.0, &MarchSettings::default())
+            .record
+            .unwrap();
+        assert_relative_eq!(f.signed_distance(rec.p), 0.0, epsilon = 1e-8);
+
+        // no sign change before it
+        let start = f.signed_distance(r.at(0.0)).signum();
+        for k in 0..1000 {
+            let t = rec.t * k as f64 / 1000.0;
+            assert_eq!(f.signed_distance(r.at(t)).signum(), start);
+        }
+    }
+
+    #[test]
+    fn test_ray_march_settings() {
+        let sphere = Sphere::new(5.0 * unit_x(), 1.0);
+        let plain = MarchSettings::default();
+        let relaxed = MarchSettings {
+            relaxation: 1.6,
+            ..Default::default()
+        };
+
+        // a relaxed step would land beyond the sphere, so it goes back and finds the front
+        let r = Ray::new(origin(), unit_x());
+        let trace = r.trace(&sphere, 0.0, 10.0, &relaxed);
+        assert_eq!(trace.outcome, Outcome::Hit);
+        assert_relative_eq!(trace.record.unwrap().p.x(), 4.0, epsilon = 1e-8);
+
+        // even when it would also land beyond the maximum parameter
+        let trace = r.trace(&sphere, 0.0, 6.0, &relaxed);
+        assert_eq!(trace.outcome, Outcome::Hit);
+        assert_relative_eq!(trace.record.unwrap().t, 4.0, epsilon = 1e-8);
+
+        // and where plain steps crawl towards a surface at a shallow angle, relaxed ones get
+        // there sooner
+        let floor = Plane::new(-unit_y(), unit_y());
+        let r = Ray::new(origin(), Vec3::new(1.0, -0.2, 0.0));
+        let (a, b) = (
+            r.trace(&floor, 0.0, 10.0, &plain),
+            r.trace(&floor, 0.0, 10.0, &relaxed),
+        );
+        assert_eq!(b.outcome, Outcome::Hit);
+        assert!(b.distance.abs() < 1e-8);
+        assert!(b.steps < a.steps, "{} vs {}", b.steps, a.steps);
+
+        // a looser tolerance far away takes fewer steps still
+        let loose = MarchSettings {
+            epsilon_per_unit: 1e-3,
+            ..Default::default()
+        };
+        assert!(r.trace(&floor, 0.0, 10.0, &loose).steps < a.steps);
+
+        // and running out of steps is reported as such
+        let short = MarchSettings {
+            max_steps: 2,
+            ..Default::default()
+        };
+        let trace = r.trace(&floor, 0.0, 10.0, &short);
+        assert_eq!(trace.outcome, Outcome::Exhausted);
+        assert_eq!(trace.steps, 2);
+    }
+
+    #[test]
+    fn test_ray_refine() {
+        // a relaxed step from the origin lands inside, at 4.56, and the hit is found between
+        let f = Equation::parse("(x - 5)^2 + y^2 + z^2 - 1", &HashMap::new())
+            .unwrap()
+            .with_lipschitz(10.0);
+        let relaxed = MarchSettings {
+            relaxation: 1.9,
+            ..Default::default()
+        };
+        let r = Ray::new(origin(), unit_x());
+        let trace = r.trace(&f, 0.0, 10.0, &relaxed);
+        assert_eq!(trace.outcome, Outcome::Hit);
+        assert_relative_eq!(trace.record.unwrap().t, 4.0, epsilon = 1e-8);
+
+        // as do steps kept from getting short, rather than wherever they first land near it
+        let floor = Plane::new(-unit_y(), unit_y());
+        let r = Ray::new(origin(), Vec3::new(1.0, -0.2, 0.0));
+        let rec = r
+            .trace(
+                &floor,
+                0.0,
+                10.0,
+                &MarchSettings {
+                    min_step: 0.5,
+                    ..Default::default()
+                },
+            )
+            .record
+            .unwrap();
+        assert_relative_eq!(rec.p.y(), -1.0, epsilon = 1e-8);
+        assert_relative_eq!(rec.t, 26.0f64.sqrt(), epsilon = 1e-7);
+    }
+
+    #[test]
+    fn test_ray_inside() {
+        let sphere = Sphere::new(5.0 * unit_x(), 1.0);
+        let march = MarchSettings::default();
+        let interval = MarchSettings {
+            method: RootFinder::Interval,
+            ..Default::default()
+        };
+
+        // from the centre out through the far side, with the normal facing back in
+        let r = Ray::new(5.0 * unit_x(), unit_x());
+        for m in [&march, &interval] {
+            let rec = r.intersect(&sphere, 0.0, 10.0, m).record.unwrap();
+            assert_relative_eq!(rec.p.x(), 6.0, epsilon = 1e-8);
+            assert!(!rec.front_face);
+            assert_relative_eq!(rec.normal.x(), -1.0);
+        }
+
+        // from on the surface, heading in and then out again, or away
+        let r = Ray::new(4.0 * unit_x(), unit_x());
+        let rec = r.trace(&sphere, 0.0, 10.0, &march).record.unwrap();
+        assert_relative_eq!(rec.p.x(), 6.0, epsilon = 1e-8);
+        let r = Ray::new(4.0 * unit_x(), -unit_x());
+        assert!(r.trace(&sphere, 0.0, 10.0, &march).record.is_none());
+
+        // but running along a surface it never gets clear of it
+        let floor = Plane::new(origin(), unit_y());
+        let r = Ray::new(origin(), unit_x());
+        for m in [&march, &interval] {
+            let trace = r.intersect(&floor, 0.0, 10.0, m);
+            assert_eq!(trace.outcome, Outcome::StartedOnSurface);
+        }
+    }
+
+    #[test]
+    fn test_ray_spawn() {
+        let sphere = Sphere::new(5.0 * unit_x(), 1.0);
+        let march = MarchSettings::default();
+        let rec = Ray::new(origin(), unit_x())
+            .trace(&sphere, 0.0, 10.0, &march)
+            .record
+            .unwrap();
+
+        // a reflected ray starts outside, clear of the surface, and finds nothing
+        let reflected = rec.spawn(-unit_x(), &march);
+        assert!(sphere.signed_distance(reflected.origin()) >= march.tolerance(rec.t));
+        assert!(reflected.trace(&sphere, 0.0, 10.0, &march).record.is_none());
+
+        // and a refracted one starts inside and finds the far side
+        let refracted = rec.spawn(unit_x(), &march);
+        assert!(sphere.signed_distance(refracted.origin()) <= -march.tolerance(rec.t));
+        let far = refracted.trace(&sphere, 0.0, 10.0, &march).record.unwrap();
+        assert_relative_eq!(far.p.x(), 6.0, epsilon = 1e-8);
+    }
 }
@@ -0,0 +1,1172 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use config::{ConfigError, Map, Value};
+use serde_derive::Deserialize;
+
+use crate::algebraic::Algebraic;
+use crate::blend::{Blend, SmoothDifference, SmoothIntersection, SmoothUnion};
+use crate::capsule::Capsule;
+use crate::cone::CappedCone;
+use crate::csg::{Difference, Intersection, Union};
+use crate::cuboid::{Cuboid, RoundedCuboid};
+use crate::cylinder::{CappedCylinder, Cylinder};
+use crate::ellipsoid::Ellipsoid;
+use crate::equation::Equation;
+use crate::hittable::{Hittable, ImplicitSurface};
+use crate::hittable_list::HittableList;
+use crate::light::Light;
+use crate::material::{Dielectric, Emissive, Lambertian, Material, Metal};
+use crate::plane::Plane;
+use crate::polynomial;
+use crate::prism::Prism;
+use crate::sphere::Sphere;
+use crate::torus::Torus;
+use crate::transform::Transformed;
+use crate::vec3::{Mat4, Vec3};
+
+// Builds the world from the [[objects]] entries of the config file, e.g.
+//
+//   [[objects]]
+//   type = "sphere"
+//   center = [0.0, 0.0, -2.0]
+//   radius = 0.75
+//
+// Each entry is a primitive or a CSG operation whose children are entries themselves, so whole
+// trees can be written inline. Every entry may also have a name, which is used in error messages,
+// and a transform, and top level ones a material. Entries are deserialized one at a time so any
+// error can say which one is wrong, as a path like objects[2].children[0]. Keys that mean nothing
+// for an entry are errors too, so a misspelt one isn't quietly left at its default.
+
+// keys any entry may have alongside its type
+const ENTRY_KEYS: [&str; 4] = ["name", "parametric", "transform", "material"];
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Entry {
+    name: Option<String>,
+    // trace a top level sphere, torus or polynomial equation analytically rather than by marching
+    // its field
+    #[serde(default)]
+    parametric: bool,
+    transform: Option<Transform>,
+    // what a top level object is made of; the parts of a CSG tree are all made of the same
+    material: Option<MaterialEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialEntry {
+    Lambertian {
+        albedo: [f64; 3],
+    },
+    Metal {
+        albedo: [f64; 3],
+        #[serde(default)]
+        fuzz: f64,
+    },
+    Dielectric {
+        refractive_index: f64,
+    },
+    Emissive {
+        colour: [f64; 3],
+    },
+}
+
+// Scale, then rotate, then translate. The angle is in degrees.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Transform {
+    #[serde(default)]
+    translate: [f64; 3],
+    rotate: Option<Rotation>,
+    #[serde(default = "unit_scale")]
+    scale: [f64; 3],
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Rotation {
+    axis: [f64; 3],
+    degrees: f64,
+}
+
+fn unit_scale() -> [f64; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum Shape {
+    Sphere {
+        center: [f64; 3],
+        radius: f64,
+    },
+    // infinite, through point along axis
+    Cylinder {
+        point: [f64; 3],
+        axis: [f64; 3],
+        radius: f64,
+    },
+    CappedCylinder {
+        a: [f64; 3],
+        b: [f64; 3],
+        radius: f64,
+    },
+    Capsule {
+        a: [f64; 3],
+        b: [f64; 3],
+        radius: f64,
+    },
+    Cone {
+        a: [f64; 3],
+        b: [f64; 3],
+        ra: f64,
+        rb: f64,
+    },
+    // with a non-zero radius the edges and corners are rounded off
+    Cuboid {
+        center: [f64; 3],
+        half_extents: [f64; 3],
+        #[serde(default)]
+        radius: f64,
+    },
+    Ellipsoid {
+        center: [f64; 3],
+        radii: [f64; 3],
+    },
+    Plane {
+        point: [f64; 3],
+        normal: [f64; 3],
+    },
+    Prism {
+        center: [f64; 3],
+        sides: usize,
+        radius: f64,
+        half_height: f64,
+    },
+    Torus {
+        center: [f64; 3],
+        axis: [f64; 3],
+        major_radius: f64,
+        minor_radius: f64,
+    },
+    // the zero set of F(x, y, z), see equation.rs for what can go in it
+    Equation {
+        equation: String,
+        #[serde(default)]
+        parameters: HashMap<String, f64>,
+        lipschitz: Option<f64>,
+    },
+    Union {
+        children: Vec<Value>,
+    },
+    Intersection {
+        children: Vec<Value>,
+    },
+    Difference {
+        base: Value,
+        tools: Vec<Value>,
+    },
+    SmoothUnion {
+        children: Vec<Value>,
+        k: f64,
+        #[serde(default)]
+        blend: Blend,
+    },
+    SmoothIntersection {
+        children: Vec<Value>,
+        k: f64,
+        #[serde(default)]
+        blend: Blend,
+    },
+    SmoothDifference {
+        base: Value,
+        tools: Vec<Value>,
+        k: f64,
+        #[serde(default)]
+        blend: Blend,
+    },
+}
+
+// The [[lights]] entries, e.g.
+//
+//   [[lights]]
+//   type = "point"
+//   position = [0.0, 2.0, 0.0]
+//   colour = [4.0, 4.0, 4.0]
+//
+// Angles are in degrees, the half angles of a spot light's cone.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum LightEntry {
+    Point {
+        position: [f64; 3],
+        colour: [f64; 3],
+    },
+    Directional {
+        direction: [f64; 3],
+        colour: [f64; 3],
+    },
+    Spot {
+        position: [f64; 3],
+        direction: [f64; 3],
+        inner_angle: f64,
+        outer_angle: f64,
+        colour: [f64; 3],
+    },
+    Area {
+        center: [f64; 3],
+        radius: f64,
+        colour: [f64; 3],
+    },
+}
+
+pub fn lights(values: &[Value]) -> Result<Vec<Light>, ConfigError> {
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| {
+            let label = format!("lights[{}]", i);
+            let entry: LightEntry = value
+                .clone()
+                .try_deserialize()
+                .map_err(|e| invalid(&label, e))?;
+            light(&label, entry)
+        })
+        .collect()
+}
+
+fn light(label: &str, entry: LightEntry) -> Result<Light, ConfigError> {
+    let colour = |c: [f64; 3]| {
+        if c.iter().all(|x| *x >= 0.0) {
+            Ok(vec(c))
+        } else {
+            Err(invalid(label, "colour must not be negative"))
+        }
+    };
+
+    Ok(match entry {
+        LightEntry::Point {
+            position,
+            colour: c,
+        } => Light::point(vec(position), colour(c)?),
+        LightEntry::Directional {
+            direction: d,
+            colour: c,
+        } => Light::directional(direction(label, "direction", d)?, colour(c)?),
+        LightEntry::Spot {
+            position,
+            direction: d,
+            inner_angle,
+            outer_angle,
+            colour: c,
+        } => {
+            if !(0.0 <= inner_angle && inner_angle <= outer_angle && outer_angle < 180.0) {
+                return Err(invalid(
+                    label,
+                    "angles must be 0 <= inner_angle <= outer_angle < 180",
+                ));
+            }
+            Light::spot(
+                vec(position),
+                direction(label, "direction", d)?,
+                inner_angle.to_radians(),
+                outer_angle.to_radians(),
+                colour(c)?,
+            )
+        }
+        LightEntry::Area {
+            center,
+            radius,
+            colour: c,
+        } => Light::area(vec(center), positive(label, "radius", radius)?, colour(c)?),
+    })
+}
+
+pub fn build(objects: &[Value]) -> Result<HittableList, ConfigError> {
+    let mut world = HittableList::new();
+
+    for (i, value) in objects.iter().enumerate() {
+        let path = format!("objects[{}]", i);
+        let (mut entry, shape, label) = parse(value, &path)?;
+        let material = material(&label, entry.material.take())?;
+
+        if !entry.parametric {
+            world.add_implicit_with_material(surface(entry, shape, &path, &label)?, material);
+            continue;
+        }
+
+        let object: Box<dyn Hittable> = match (shape, &entry.transform) {
+            (Shape::Sphere { center, radius }, None) => {
+                let radius = positive(&label, "radius", radius)?;
+                Box::new(Sphere::new(vec(center), radius))
+            }
+            (
+                Shape::Torus {
+                    center,
+                    axis,
+                    major_radius,
+                    minor_radius,
+                },
+                None,
+            ) => Box::new(Algebraic::torus(
+                vec(center),
+                direction(&label, "axis", axis)?,
+                positive(&label, "major_radius", major_radius)?,
+                positive(&label, "minor_radius", minor_radius)?,
+            )),
+            (
+                Shape::Equation {
+                    lipschitz: Some(_), ..
+                },
+                None,
+            ) => {
+                return Err(invalid(
+                    &label,
+                    "a parametric equation is solved exactly, so can't have a lipschitz bound",
+                ))
+            }
+            (
+                Shape::Equation {
+                    equation: source,
+                    parameters,
+                    ..
+                },
+                None,
+            ) => match equation(&label, &source, &parameters)?.polynomial() {
+                Some(f) => Box::new(Algebraic::new(f)),
+                None => {
+                    return Err(invalid(
+                        &label,
+                        format!(
+                            "a parametric equation must be a polynomial in x, y and z of degree at most {}",
+                            polynomial::MAX_DEGREE
+                        ),
+                    ))
+                }
+            },
+            _ => return Err(invalid(
+                &label,
+                "only spheres, tori and polynomial equations without a transform can be parametric",
+            )),
+        };
+        world.add_with_material(object, material);
+    }
+
+    Ok(world)
+}
+
+// The entry at path, along with how to refer to it in errors. The keys any entry may have are
+// split off from those of its shape, so each part can reject keys it doesn't know.
+fn parse(value: &Value, path: &str) -> Result<(Entry, Shape, String), ConfigError> {
+    let Ok(mut table) = value.clone().into_table() else {
+        return Err(invalid(path, "expected a table describing an object"));
+    };
+    let common: Map<String, Value> = ENTRY_KEYS
+        .iter()
+        .filter_map(|k| table.remove_entry(*k))
+        .collect();
+
+    let entry: Entry = Value::from(common)
+        .try_deserialize()
+        .map_err(|e| invalid(path, e))?;
+
+    let label = match &entry.name {
+        Some(name) => format!("{} ({:?})", path, name),
+        None => path.to_string(),
+    };
+
+    let shape: Shape = Value::from(table)
+        .try_deserialize()
+        .map_err(|e| invalid(&label, e))?;
+
+    Ok((entry, shape, label))
+}
+
+fn child(value: &Value, path: &str) -> Result<Box<dyn ImplicitSurface>, ConfigError> {
+    let (entry, shape, label) = parse(value, path)?;
+    if entry.parametric {
+        return Err(invalid(&label, "only top level objects can be parametric"));
+    }
+    if entry.material.is_some() {
+        return Err(invalid(
+            &label,
+            "only top level objects can have a material",
+        ));
+    }
+    surface(entry, shape, path, &label)
+}
+
+fn children(
+    values: &[Value],
+    path: &str,
+    key: &str,
+) -> Result<Vec<Box<dyn ImplicitSurface>>, ConfigError> {
+    if values.is_empty() {
+        return Err(invalid(path, format!("{} must not be empty", key)));
+    }
+
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, v)| child(v, &format!("{}.{}[{}]", path, key, i)))
+        .collect()
+}
+
+fn surface(
+    entry: Entry,
+    shape: Shape,
+    path: &str,
+    label: &str,
+) -> Result<Box<dyn ImplicitSurface>, ConfigError> {
+    let positive = |what: &str, x: f64| positive(label, what, x);
+    let direction = |what: &str, v: [f64; 3]| direction(label, what, v);
+    let apart = |a: [f64; 3], b: [f64; 3]| {
+        if a == b {
+            Err(invalid(label, "a and b must be different points"))
+        } else {
+            Ok((vec(a), vec(b)))
+        }
+    };
+
+    let s: Box<dyn ImplicitSurface> = match shape {
+        Shape::Sphere { center, radius } => {
+            Box::new(Sphere::new(vec(center), positive("radius", radius)?))
+        }
+        Shape::Cylinder {
+            point,
+            axis,
+            radius,
+        } => Box::new(Cylinder::new(
+            vec(point),
+            direction("axis", axis)?,
+            positive("radius", radius)?,
+        )),
+        Shape::CappedCylinder { a, b, radius } => {
+            let (a, b) = apart(a, b)?;
+            Box::new(CappedCylinder::new(a, b, positive("radius", radius)?))
+        }
+        Shape::Capsule { a, b, radius } => {
+            let (a, b) = apart(a, b)?;
+            Box::new(Capsule::new(a, b, positive("radius", radius)?))
+        }
+        Shape::Cone { a, b, ra, rb } => {
+            let (a, b) = apart(a, b)?;
+            if ra < 0.0 || rb < 0.0 || ra + rb == 0.0 {
+                return Err(invalid(
+                    label,
+                    "ra and rb must not be negative, and not both zero",
+                ));
+            }
+            Box::new(CappedCone::new(a, b, ra, rb))
+        }
+        Shape::Cuboid {
+            center,
+            half_extents,
+            radius,
+        } => {
+            for h in half_extents {
+                positive("half_extents", h)?;
+            }
+            if radius < 0.0 {
+                return Err(invalid(label, "radius must not be negative"));
+            }
+            if radius == 0.0 {
+                Box::new(Cuboid::new(vec(center), vec(half_extents)))
+            } else {
+                Box::new(RoundedCuboid::new(vec(center), vec(half_extents), radius))
+            }
+        }
+        Shape::Ellipsoid { center, radii } => {
+            for r in radii {
+                positive("radii", r)?;
+            }
+            Box::new(Ellipsoid::new(vec(center), vec(radii)))
+        }
+        Shape::Plane { point, normal } => {
+            Box::new(Plane::new(vec(point), direction("normal", normal)?))
+        }
+        Shape::Prism {
+            center,
+            sides,
+            radius,
+            half_height,
+        } => {
+            if sides < 3 {
+                return Err(invalid(label, "a prism needs at least 3 sides"));
+            }
+            Box::new(Prism::new(
+                vec(center),
+                sides,
+                positive("radius", radius)?,
+                positive("half_height", half_height)?,
+            ))
+        }
+        Shape::Torus {
+            center,
+            axis,
+            major_radius,
+            minor_radius,
+        } => Box::new(Torus::new(
+            vec(center),
+            direction("axis", axis)?,
+            positive("major_radius", major_radius)?,
+            positive("minor_radius", minor_radius)?,
+        )),
+        Shape::Equation {
+            equation,
+            parameters,
+            lipschitz,
+        } => {
+            let e = self::equation(label, &equation, &parameters)?;
+            match lipschitz {
+                Some(l) => Box::new(e.with_lipschitz(positive("lipschitz", l)?)),
+                None => Box::new(e),
+            }
+        }
+        Shape::Union { children: c } => {
+            Box::new(Union::from_children(children(&c, path, "children")?))
+        }
+        Shape::Intersection { children: c } => {
+            Box::new(Intersection::from_children(children(&c, path, "children")?))
+        }
+        Shape::Difference { base, tools } => Box::new(Difference::from_children(
+            child(&base, &format!("{}.base", path))?,
+            children(&tools, path, "tools")?,
+        )),
+        Shape::SmoothUnion {
+            children: c,
+            k,
+            blend,
+        } => {
+            let k = positive("k", k)?;
+            fold(children(&c, path, "children")?, |a, b| {
+                Box::new(SmoothUnion::new(a, b, k, blend))
+            })
+        }
+        Shape::SmoothIntersection {
+            children: c,
+            k,
+            blend,
+        } => {
+            let k = positive("k", k)?;
+            fold(children(&c, path, "children")?, |a, b| {
+                Box::new(SmoothIntersection::new(a, b, k, blend))
+            })
+        }
+        Shape::SmoothDifference {
+            base,
+            tools,
+            k,
+            blend,
+        } => {
+            let k = positive("k", k)?;
+            let mut all = vec![child(&base, &format!("{}.base", path))?];
+            all.extend(children(&tools, path, "tools")?);
+            fold(all, |a, b| Box::new(SmoothDifference::new(a, b, k, blend)))
+        }
+    };
+
+    let Some(t) = entry.transform else {
+        return Ok(s);
+    };
+    let t = Transformed::new(s, matrix(&t, label)?);
+    if !t.is_uniform() {
+        eprintln!(
+            "warning: {}: non-uniform scale in transform, distances will be a lower bound",
+            label
+        );
+    }
+    Ok(Box::new(t))
+}
+
+// the smooth operations take two children, so more are combined from the left
+fn fold(
+    children: Vec<Box<dyn ImplicitSurface>>,
+    op: impl Fn(Box<dyn ImplicitSurface>, Box<dyn ImplicitSurface>) -> Box<dyn ImplicitSurface>,
+) -> Box<dyn ImplicitSurface> {
+    let mut it = children.into_iter();
+    let first = it.next().expect("children are never empty");
+    it.fold(first, op)
+}
+
+fn matrix(t: &Transform, label: &str) -> Result<Mat4, ConfigError> {
+    if t.scale.contains(&0.0) {
+        return Err(invalid(label, "transform scale must not be zero"));
+    }
+
+    let rotation = match &t.rotate {
+        None => Mat4::identity(),
+        Some(r) => Mat4::rotation(
+            direction(label, "transform axis", r.axis)?,
+            r.degrees.to_radians(),
+        ),
+    };
+
+    Ok(Mat4::translation(vec(t.translate)) * rotation * Mat4::scaling(vec(t.scale)))
+}
+
+// matte grey if none is given
+fn material(label: &str, m: Option<MaterialEntry>) -> Result<Rc<dyn Material>, ConfigError> {
+    let colour = |what: &str, c: [f64; 3], max: f64| {
+        if c.iter().all(|x| (0.0..=max).contains(x)) {
+            Ok(vec(c))
+        } else {
+            Err(invalid(
+                label,
+                format!("material {} must be between 0 and {}", what, max),
+            ))
+        }
+    };
+
+    Ok(match m {
+        None => Rc::new(Lambertian::default()),
+        Some(MaterialEntry::Lambertian { albedo }) => {
+            Rc::new(Lambertian::new(colour("albedo", albedo, 1.0)?))
+        }
+        Some(MaterialEntry::Metal { albedo, fuzz }) => {
+            if !(0.0..=1.0).contains(&fuzz) {
+                return Err(invalid(label, "material fuzz must be between 0 and 1"));
+            }
+            Rc::new(Metal::new(colour("albedo", albedo, 1.0)?, fuzz))
+        }
+        Some(MaterialEntry::Dielectric { refractive_index }) => Rc::new(Dielectric::new(positive(
+            label,
+            "refractive_index",
+            refractive_index,
+        )?)),
+        Some(MaterialEntry::Emissive { colour: c }) => {
+            Rc::new(Emissive::new(colour("colour", c, f64::INFINITY)?))
+        }
+    })
+}
+
+fn vec(v: [f64; 3]) -> Vec3 {
+    Vec3::new(v[0], v[1], v[2])
+}
+
+fn equation(
+    label: &str,
+    source: &str,
+    parameters: &HashMap<String, f64>,
+) -> Result<Equation, ConfigError> {
+    if let Some(p) = ["x", "y", "z"]
+        .iter()
+        .find(|v| parameters.contains_key(**v))
+    {
+        return Err(invalid(label, format!("parameter {} hides a variable", p)));
+    }
+    Equation::parse(source, parameters).map_err(|e| invalid(label, format!("in equation, {}", e)))
+}
+
+pub(crate) fn positive(label: &str, what: &str, x: f64) -> Result<f64, ConfigError> {
+    if x > 0.0 {
+        Ok(x)
+    } else {
+        Err(invalid(
+            label,
+            format!("{} must be positive, got {}", what, x),
+        ))
+    }
+}
+
+fn direction(label: &str, what: &str, v: [f64; 3]) -> Result<Vec3, ConfigError> {
+    if v == [0.0; 3] {
+        Err(invalid(label, format!("{} must not be zero", what)))
+    } else {
+        Ok(vec(v))
+    }
+}
+
+pub(crate) fn invalid(label: &str, e: impl std::fmt::Display) -> ConfigError {
+    ConfigError::Message(format!("{}: {}", label, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use config::{Config, File, FileFormat};
+
+    use crate::diagnostics::Diagnostics;
+    use crate::ray::{MarchSettings, Ray};
+    use crate::vec3::{origin, unit_x, unit_y};
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    fn objects(toml: &str) -> Vec<Value> {
+        Config::builder()
+            .add_source(File::from_str(toml, FileFormat::Toml))
+            .build()
+            .unwrap()
+            .get("objects")
+            .unwrap()
+    }
+
+    fn error(toml: &str) -> String {
+        match build(&objects(toml)) {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_scene_primitives() {
+        let world = build(&objects(
+            r#"
+            [[objects]]
+            type = "sphere"
+            center = [0.0, 0.0, 0.0]
+            radius = 1
+
+            [[objects]]
+            type = "cuboid"
+            center = [3.0, 0.0, 0.0]
+            half_extents = [0.5, 0.5, 0.5]
+
+            [[objects]]
+            type = "sphere"
+            center = [0.0, -100.0, 0.0]
+            radius = 10
+            parametric = true
+            "#,
+        ))
+        .unwrap();
+
+        // the parametric sphere isn't part of the implicit field
+        assert_relative_eq!(world.signed_distance(origin()), -1.0);
+        assert_relative_eq!(world.signed_distance(2.0 * unit_x()), 0.5);
+        assert_relative_eq!(world.signed_distance(-100.0 * unit_y()), 99.0);
+    }
+
+    #[test]
+    fn test_scene_csg_and_transform() {
+        let world = build(&objects(
+            r#"
+            [[objects]]
+            type = "difference"
+            base = { type = "cuboid", center = [0, 0, 0], half_extents = [1, 1, 1] }
+
+            [[objects.tools]]
+            type = "sphere"
+            center = [0, 0, 0]
+            radius = 0.5
+            transform = { translate = [0, 0, 1] }
+
+            [[objects.tools]]
+            type = "sphere"
+            center = [0, 0, 0]
+            radius = 0.5
+            transform = { translate = [0, 0, -1] }
+            "#,
+        ))
+        .unwrap();
+
+        assert_relative_eq!(world.signed_distance(Vec3::new(0.0, 0.0, 1.0)), 0.5);
+        assert_relative_eq!(world.signed_distance(Vec3::new(0.0, 0.0, -0.75)), 0.25);
+        assert_relative_eq!(world.signed_distance(origin()), -0.5);
+    }
+
+    #[test]
+    fn test_scene_parametric() {
+        let world = build(&objects(
+            r#"
+            [[objects]]
+            type = "torus"
+            center = [0, 0, 0]
+            axis = [0, 0, 1]
+            major_radius = 2
+            minor_radius = 0.5
+            parametric = true
+
+            [[objects]]
+            type = "equation"
+            equation = "(x - 10)^2 + y^2 + z^2 - r^2"
+            parameters = { r = 1 }
+            parametric = true
+            "#,
+        ))
+        .unwrap();
+
+        // neither is part of the implicit field
+        assert_eq!(world.signed_distance(origin()), f64::INFINITY);
+
+        let r = Ray::new(-5.0 * unit_x(), unit_x());
+        let rec = world
+            .hit(
+                &r,
+                0.0,
+                f64::INFINITY,
+                &MarchSettings::default(),
+                &mut Diagnostics::default(),
+            )
+            .unwrap();
+        assert_relative_eq!(rec.t, 2.5, epsilon = 1e-9);
+
+        let rec = world
+            .hit(
+                &r,
+                8.0,
+                f64::INFINITY,
+                &MarchSettings::default(),
+                &mut Diagnostics::default(),
+            )
+            .unwrap();
+        assert_relative_eq!(rec.t, 14.0, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_scene_equation() {
+        let world = build(&objects(
+            r#"
+            [[objects]]
+            type = "equation"
+            equation = "max(abs(x), abs(y), abs(z)) - size"
+            parameters = { size = 0.5 }
+
+            [[objects]]
+            type = "equation"
+            equation = "x^2 + y^2 + z^2 - 100"
+            lipschitz = 40
+            transform = { translate = [0, 100, 0] }
+            "#,
+        ))
+        .unwrap();
+
+        assert_relative_eq!(world.signed_distance(2.0 * unit_x()), 1.5);
+        assert_relative_eq!(world.signed_distance(origin()), -0.5);
+        assert_relative_eq!(world.lipschitz(origin(), 1.0), 40.0);
+    }
+
+    #[test]
+    fn test_scene_materials() {
+        let world = build(&objects(
+            r#"
+            [[objects]]
+            type = "sphere"
+            center = [0, 0, 0]
+            radius = 1
+            material = { type = "emissive", colour = [4, 4, 4] }
+
+            [[objects]]
+            type = "sphere"
+            center = [0, 0, 0]
+            radius = 10
+            parametric = true
+            material = { type = "metal", albedo = [0.8, 0.6, 0.2] }
+
+            [[objects]]
+            type = "cuboid"
+            center = [5, 0, 0]
+            half_extents = [1, 1, 1]
+            "#,
+        ))
+        .unwrap();
+
+        let hit = |origin: Vec3, direction: Vec3| {
+            world
+                .hit(
+                    &Ray::new(origin, direction),
+                    0.0,
+                    f64::INFINITY,
+                    &MarchSettings::default(),
+                    &mut Diagnostics::default(),
+                )
+                .unwrap()
+        };
+
+        // each hit carries the material of what it hit
+        let light = hit(-5.0 * unit_x(), unit_x());
+        let m = light.material.as_ref().unwrap();
+        assert_relative_eq!(m.emitted(&light).x(), 4.0);
+
+        let r = Ray::new(2.0 * unit_y(), unit_y());
+        let mirror = hit(r.origin(), r.direction());
+        let (attenuation, d) = mirror
+            .material
+            .as_ref()
+            .unwrap()
+            .scatter(&r, &mirror)
+            .unwrap();
+        assert_relative_eq!(attenuation.y(), 0.6);
+        assert_relative_eq!(d.y(), -1.0);
+
+        // and those without one are matte grey
+        let r = Ray::new(2.0 * unit_x(), unit_x());
+        let grey = hit(r.origin(), r.direction());
+        let (attenuation, _) = grey.material.as_ref().unwrap().scatter(&r, &grey).unwrap();
+        assert_relative_eq!(attenuation.z(), 0.5);
+
+        let e = error(
+            r#"
+            [[objects]]
+            type = "sphere"
+            center = [0, 0, 0]
+            radius = 1
+            material = { type = "lambertian", albedo = [0.5, 1.5, 0.5] }
+            "#,
+        );
+        assert_eq!(e, "objects[0]: material albedo must be between 0 and 1");
+
+        let e = error(
+            r#"
+            [[objects]]
+            type = "union"
+
+            [[objects.children]]
+            type = "sphere"
+            center = [0, 0, 0]
+            radius = 1
+            material = { type = "dielectric", refractive_index = 1.5 }
+            "#,
+        );
+        assert_eq!(
+            e,
+            "objects[0].children[0]: only top level objects can have a material"
+        );
+    }
+
+    #[test]
+    fn test_scene_lights() {
+        let lights = |toml: &str| {
+            let values: Vec<Value> = Config::builder()
+                .add_source(File::from_str(toml, FileFormat::Toml))
+                .build()
+                .unwrap()
+                .get("lights")
+                .unwrap();
+            lights(&values)
+        };
+
+        let l = lights(
+            r#"
+            [[lights]]
+            type = "point"
+            position = [0, 2, 0]
+            colour = [4, 4, 4]
+
+            [[lights]]
+            type = "area"
+            center = [0, 10, 0]
+            radius = 1
+            colour = [1, 1, 1]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(l.len(), 2);
+        assert_relative_eq!(l[0].sample(origin()).unwrap().radiance.x(), 1.0);
+        assert!(l[1].hit(&Ray::new(origin(), unit_y())).is_some());
+
+        let e = lights(
+            r#"
+            [[lights]]
+            type = "spot"
+            position = [0, 2, 0]
+            direction = [0, -1, 0]
+            inner_angle = 30
+            outer_angle = 20
+            colour = [1, 1, 1]
+            "#,
+        );
+        assert_eq!(
+            e.err().unwrap().to_string(),
+            "lights[0]: angles must be 0 <= inner_angle <= outer_angle < 180"
+        );
+    }
+
+    #[test]
+    fn test_scene_errors_name_entry() {
+        let e = error(
+            r#"
+            [[objects]]
+            type = "sphere"
+            center = [0, 0, 0]
+            radius = 1
+
+            [[objects]]
+            type = "spehre"
+            "#,
+        );
+        assert!(
+            e.starts_with("objects[1]: unknown variant `spehre`"),
+            "{}",
+            e
+        );
+
+        let e = error(
+            r#"
+            [[objects]]
+            name = "body"
+            type = "torus"
+            center = [0, 0, 0]
+            axis = [0, 1, 0]
+            major_radius = 1
+            "#,
+        );
+        assert!(
+            e.starts_with("objects[0] (\"body\"): missing field `minor_radius`"),
+            "{}",
+            e
+        );
+
+        let e = error(
+            r#"
+            [[objects]]
+            type = "union"
+
+            [[objects.children]]
+            type = "sphere"
+            center = [0, 0, 0]
+            radius = 1
+
+            [[objects.children]]
+            type = "sphere"
+            center = [0, 0, 0]
+            radius = -1
+            "#,
+        );
+        assert_eq!(e, "objects[0].children[1]: radius must be positive, got -1");
+
+        let e = error(
+            r#"
+            [[objects]]
+            name = "blob"
+            type = "equation"
+            equation = "x^2 + y^2 + z^2 - r^"
+            parameters = { r = 1 }
+            "#,
+        );
+        assert_eq!(
+            e,
+            "objects[0] (\"blob\"): in equation, column 21: expected a value but found end of equation"
+        );
+
+        let e = error(
+            r#"
+            [[objects]]
+            type = "plane"
+            point = [0, 0, 0]
+            normal = [0, 1, 0]
+            parametric = true
+            "#,
+        );
+        assert_eq!(
+            e,
+            "objects[0]: only spheres, tori and polynomial equations without a transform can be parametric"
+        );
+
+        let e = error(
+            r#"
+            [[objects]]
+            type = "equation"
+            equation = "sqrt(x^2 + y^2) - 1"
+            parametric = true
+            "#,
+        );
+        assert_eq!(
+            e,
+            "objects[0]: a parametric equation must be a polynomial in x, y and z of degree at most 16"
+        );
+
+        let e = error(
+            r#"
+            [[objects]]
+            type = "equation"
+            equation = "x^100000000 + y^2 + z^2 - 1"
+            parametric = true
+            "#,
+        );
+        assert!(e.ends_with("of degree at most 16"), "{}", e);
+
+        let e = error(
+            r#"
+            [[objects]]
+            type = "equation"
+            equation = "x^2 + y^2 + z^2 - 1"
+            lipschitz = 4
+            parametric = true
+            "#,
+        );
+        assert_eq!(
+            e,
+            "objects[0]: a parametric equation is solved exactly, so can't have a lipschitz bound"
+        );
+
+        let e = error(
+            r#"
+            [[objects]]
+            type = "capsule"
+            a = [0, 1, 0]
+            b = [0, 1, 0]
+            radius = 0.5
+            "#,
+        );
+        assert_eq!(e, "objects[0]: a and b must be different points");
+    }
+
+    #[test]
+    fn test_scene_unknown_keys() {
+        // misspelt, so it would otherwise be the default
+        let e = error(
+            r#"
+            [[objects]]
+            type = "sphere"
+            center = [0, 0, 0]
+            radius = 1
+            parametirc = true
+            "#,
+        );
+        assert!(
+            e.starts_with("objects[0]: unknown field `parametirc`"),
+            "{}",
+            e
+        );
+
+        let e = error(
+            r#"
+            [[objects]]
+            name = "ball"
+            type = "sphere"
+            center = [0, 0, 0]
+            radius = 1
+            height = 2
+            "#,
+        );
+        assert!(
+            e.starts_with("objects[0] (\"ball\"): unknown field `height`"),
+            "{}",
+            e
+        );
+
+        for extra in [
+            "transform = { translate = [1, 0, 0], scael = [2, 2, 2] }",
+            "transform = { rotate = { axis = [0, 1, 0], degrees = 30, radians = 1 } }",
+            "material = { type = \"metal\", albedo = [1, 1, 1], fuz = 0.1 }",
+        ] {
+            let e = error(&format!(
+                "[[objects]]\ntype = \"sphere\"\ncenter = [0, 0, 0]\nradius = 1\n{}",
+                extra
+            ));
+            assert!(e.contains("unknown field"), "{}", e);
+        }
+
+        let values: Vec<Value> = Config::builder()
+            .add_source(File::from_str(
+                r#"
+                [[lights]]
+                type = "point"
+                position = [0, 2, 0]
+                colour = [4, 4, 4]
+                radius = 1
+                "#,
+                FileFormat::Toml,
+            ))
+            .build()
+            .unwrap()
+            .get("lights")
+            .unwrap();
+        let e = lights(&values).err().unwrap().to_string();
+        assert!(e.starts_with("lights[0]: unknown field `radius`"), "{}", e);
+    }
+}
@@ -1,7 +1,14 @@
+use std::path::Path;
+
 use clap::Parser;
-use config::{Config, ConfigError, File};
+use config::{Config, ConfigError, File, Value};
 use serde_derive::Deserialize;
 
+use crate::camera::CameraSettings;
+use crate::ray::MarchSettings;
+use crate::scene::{invalid, positive};
+use crate::shading::ShadingSettings;
+
 #[derive(Debug, Deserialize)]
 pub struct View {
     pub height: u64,
@@ -14,20 +21,136 @@ pub struct Sampling {
     pub samples_per_pixel: u64,
 }
 
+// how STL and PLY files are written; OBJ is always text
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    Ascii,
+    #[default]
+    Binary,
+}
+
+// the kinds of mesh file, by extension
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MeshFile {
+    Stl,
+    Obj,
+    Ply,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MeshMethod {
+    #[default]
+    MarchingCubes,
+    // keeps sharp edges and corners, e.g. from CSG or boxes
+    DualContouring,
+}
+
+// per-vertex scalars that can be added to a mesh, which only PLY files keep
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum VertexAttribute {
+    // how far each vertex is from the surface it approximates
+    Distance,
+}
+
+// Polygonize the implicit surfaces in the scene and write them out as STL, OBJ or PLY, chosen by
+// the extension of output
 #[derive(Debug, Deserialize)]
+pub struct Mesh {
+    pub output: String,
+    #[serde(default)]
+    pub format: Encoding,
+    #[serde(default)]
+    pub method: MeshMethod,
+    // opposite corners of the box to polygonize within
+    pub min: [f64; 3],
+    pub max: [f64; 3],
+    // number of cells along the longest side of the box
+    pub resolution: usize,
+    // only refine cells near the surface, with an octree whose finest level has resolution
+    // rounded up to a power of two cells along each side
+    #[serde(default)]
+    pub adaptive: bool,
+    #[serde(default)]
+    pub attributes: Vec<VertexAttribute>,
+}
 
+impl Mesh {
+    // the kind of file output names, if any
+    pub fn file(&self) -> Option<MeshFile> {
+        let extension = Path::new(&self.output).extension()?.to_str()?;
+        match extension.to_ascii_lowercase().as_str() {
+            "stl" => Some(MeshFile::Stl),
+            "obj" => Some(MeshFile::Obj),
+            "ply" => Some(MeshFile::Ply),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
 pub struct Settings {
     pub output: String,
     pub view: View,
     pub sampling: Sampling,
+    #[serde(default)]
+    pub camera: CameraSettings,
+    #[serde(default)]
+    pub marching: MarchSettings,
+    #[serde(default)]
+    pub shading: ShadingSettings,
+    pub mesh: Option<Mesh>,
+    // the [[objects]] making up the world, built by scene::build
+    #[serde(default)]
+    pub objects: Vec<Value>,
+    // the [[lights]] shining on it, built by scene::lights
+    #[serde(default)]
+    pub lights: Vec<Value>,
 }
 
 impl Settings {
     pub fn new(p: &str) -> Result<Self, ConfigError> {
         let s = Config::builder().add_source(File::with_name(p)).build()?;
 
-        s.try_deserialize()
+        let settings: Settings = s.try_deserialize()?;
+        check_marching(&settings.marching)?;
+        check_shading(&settings.shading)?;
+        if let Some(m) = &settings.mesh {
+            check_mesh(m)?;
+        }
+        Ok(settings)
+    }
+}
+
+fn check_marching(m: &MarchSettings) -> Result<(), ConfigError> {
+    positive("marching", "epsilon", m.epsilon)?;
+    positive("marching", "min_step", m.min_step)?;
+    positive("marching", "max_steps", m.max_steps as f64)?;
+    if !(1.0..2.0).contains(&m.relaxation) {
+        return Err(invalid(
+            "marching",
+            format!("relaxation must be from 1 up to 2, got {}", m.relaxation),
+        ));
+    }
+    Ok(())
+}
+
+fn check_shading(s: &ShadingSettings) -> Result<(), ConfigError> {
+    positive("shading", "softness", s.softness)?;
+    positive("shading", "occlusion_step", s.occlusion_step)?;
+    Ok(())
+}
+
+fn check_mesh(m: &Mesh) -> Result<(), ConfigError> {
+    if m.file().is_none() {
+        return Err(invalid(
+            "mesh",
+            format!("output {} should end in .stl, .obj or .ply", m.output),
+        ));
     }
+    Ok(())
 }
 
 /// Ray tracer to view implicit surfaces
@@ -37,4 +160,111 @@ pub struct Args {
     /// path to a config file
     #[arg(short, long, default_value = "config.toml")]
     pub config: String,
+
+    /// compare the analytic gradients of each implicit surface and the scene against numeric ones,
+    /// then exit without rendering
+    #[arg(long)]
+    pub check_gradients: bool,
+
+    /// step used for the numeric gradients when checking
+    #[arg(long, default_value_t = 1e-6)]
+    pub epsilon: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use config::FileFormat;
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_settings_example_config() {
+        let cfg = Settings::new("config.toml").unwrap();
+        assert_eq!(cfg.marching, MarchSettings::default());
+        assert_eq!(cfg.shading, ShadingSettings::default());
+    }
+
+    #[test]
+    fn test_settings_check_marching() {
+        assert!(check_marching(&MarchSettings::default()).is_ok());
+
+        let bad = [
+            MarchSettings {
+                epsilon: 0.0,
+                ..Default::default()
+            },
+            MarchSettings {
+                min_step: -1.0,
+                ..Default::default()
+            },
+            MarchSettings {
+                max_steps: 0,
+                ..Default::default()
+            },
+            MarchSettings {
+                relaxation: 0.9,
+                ..Default::default()
+            },
+            MarchSettings {
+                relaxation: 2.0,
+                ..Default::default()
+            },
+        ];
+        for m in &bad {
+            assert!(check_marching(m).is_err(), "{:?}", m);
+        }
+
+        let e = check_marching(&bad[3]).unwrap_err().to_string();
+        assert_eq!(e, "marching: relaxation must be from 1 up to 2, got 0.9");
+    }
+
+    #[test]
+    fn test_settings_check_shading() {
+        assert!(check_shading(&ShadingSettings::default()).is_ok());
+
+        let flat = ShadingSettings {
+            softness: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            check_shading(&flat).unwrap_err().to_string(),
+            "shading: softness must be positive, got 0"
+        );
+        let still = ShadingSettings {
+            occlusion_step: -0.05,
+            ..Default::default()
+        };
+        assert!(check_shading(&still).is_err());
+    }
+
+    #[test]
+    fn test_settings_check_mesh() {
+        let mesh = |output: &str| -> Mesh {
+            let toml = format!(
+                "output = \"{}\"\nmin = [-1, -1, -1]\nmax = [1, 1, 1]\nresolution = 8",
+                output
+            );
+            Config::builder()
+                .add_source(File::from_str(&toml, FileFormat::Toml))
+                .build()
+                .unwrap()
+                .try_deserialize()
+                .unwrap()
+        };
+
+        assert_eq!(mesh("out.stl").file(), Some(MeshFile::Stl));
+        assert_eq!(mesh("dir.v2/out.OBJ").file(), Some(MeshFile::Obj));
+        assert_eq!(mesh("out.ply").file(), Some(MeshFile::Ply));
+        assert!(mesh("out.ply").attributes.is_empty());
+        assert!(check_mesh(&mesh("out.ply")).is_ok());
+
+        for bad in ["out.off", "out", "ply"] {
+            assert!(check_mesh(&mesh(bad)).is_err(), "{}", bad);
+        }
+        assert_eq!(
+            check_mesh(&mesh("out.off")).unwrap_err().to_string(),
+            "mesh: output out.off should end in .stl, .obj or .ply"
+        );
+    }
 }
@@ -0,0 +1,265 @@
+use serde_derive::Deserialize;
+
+use crate::colour::Colour;
+use crate::common;
+use crate::diagnostics::Diagnostics;
+use crate::hittable::ImplicitSurface;
+use crate::hittable_list::HittableList;
+use crate::light::Light;
+use crate::ray::{MarchSettings, Ray};
+use crate::vec3::{self, dot, Vec3};
+
+// how each pixel's colour is found
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ShadingMode {
+    // following rays as they bounce around the scene, which needs many samples to converge
+    #[default]
+    PathTraced,
+    // Shading each hit once from what the distance field tells us about its surroundings, with
+    // soft shadows and ambient occlusion. Not physically right, but it looks good with a single
+    // sample per pixel.
+    Preview,
+}
+
+// The [shading] section of the config
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ShadingSettings {
+    pub mode: ShadingMode,
+    // how sharp shadows are, larger is harder
+    pub softness: f64,
+    // ambient occlusion looks at the field this many times along the normal, this far apart, and
+    // darkens by strength times how much nearer than that the surfaces there are
+    pub occlusion_samples: usize,
+    pub occlusion_step: f64,
+    pub occlusion_strength: f64,
+}
+
+impl Default for ShadingSettings {
+    fn default() -> ShadingSettings {
+        ShadingSettings {
+            mode: ShadingMode::default(),
+            softness: 8.0,
+            occlusion_samples: 5,
+            occlusion_step: 0.05,
+            occlusion_strength: 1.5,
+        }
+    }
+}
+
+// the light coming from the sky in direction
+pub fn sky(direction: Vec3) -> Colour {
+    let n = vec3::normalise(direction);
+    let t = 0.5 * (n.y() + 1.0);
+    (1.0 - t) * Colour::new(1.0, 1.0, 1.0) + t * Colour::new(0.5, 0.7, 1.0)
+}
+
+// Soft shadows (Quilez 2010). March towards the light as sphere tracing does, and wherever the ray
+// passes within d of a surface t along it, the light is taken to be that much covered, as if it
+// had an angular size of about 1 / softness: k d / t, or 1 for fully visible. A ray that hits is
+// in full shadow.
+pub fn soft_shadow(
+    su: &dyn ImplicitSurface,
+    r: &Ray,
+    t_max: f64,
+    softness: f64,
+    march: &MarchSettings,
+) -> f64 {
+    let mut visible: f64 = 1.0;
+    let mut t = 0.0;
+
+    for _ in 0..march.max_steps {
+        let v = r.at(t);
+        let d = su.signed_distance(v);
+        if d < march.tolerance(t) {
+            return 0.0;
+        }
+
+        // as a distance, whatever the field's Lipschitz bound
+        let reach = d / su.lipschitz(v, 0.0);
+        let bound = reach.min(d / su.lipschitz(v, reach));
+        if t > 0.0 {
+            visible = visible.min(softness * bound / t);
+        }
+
+        t += bound.max(march.min_step);
+        if t > t_max {
+            break;
+        }
+    }
+
+    common::clamp(visible, 0.0, 1.0)
+}
+
+// Ambient occlusion. Points along the normal at h are h from the surface unless something else
+// is nearer, so the shortfall at each says how enclosed the hit is, nearer points counting more.
+// 1 is open to the sky, 0 fully enclosed.
+pub fn ambient_occlusion(
+    su: &dyn ImplicitSurface,
+    p: Vec3,
+    normal: Vec3,
+    settings: &ShadingSettings,
+) -> f64 {
+    let mut occlusion = 0.0;
+    let mut weight = 1.0;
+
+    for i in 1..=settings.occlusion_samples {
+        let h = i as f64 * settings.occlusion_step;
+        let d = su.signed_distance(p + h * normal);
+        occlusion += weight * (h - d).max(0.0);
+        weight *= 0.5;
+    }
+
+    common::clamp(
+        1.0 - settings.occlusion_strength * occlusion / settings.occlusion_step,
+        0.0,
+        1.0,
+    )
+}
+
+// The preview colour of the first hit along r: matte, lit by the sky as far as ambient occlusion
+// lets it and by each light as far as its soft shadow does. Parametric surfaces have no field to
+// march, so they cast hard shadows. Every material is shaded as matte in its albedo.
+pub fn preview(
+    r: &Ray,
+    world: &HittableList,
+    lights: &[Light],
+    march: &MarchSettings,
+    settings: &ShadingSettings,
+    diagnostics: &mut Diagnostics,
+) -> Colour {
+    let Some(rec) = world.hit(r, 0.0, f64::INFINITY, march, diagnostics) else {
+        return sky(r.direction());
+    };
+    let material = rec
+        .material
+        .as_ref()
+        .expect("every hit in the world has a material");
+    let albedo = material.albedo();
+
+    // the sky over the hemisphere around the normal, as if it were all the colour straight up it
+    let ao = ambient_occlusion(world, rec.p, rec.normal, settings);
+    let mut light = ao * sky(rec.normal);
+
+    for l in lights {
+        let Some(sample) = l.sample(rec.p) else {
+            continue;
+        };
+        let cos = dot(sample.direction, rec.normal);
+        if cos <= 0.0 {
+            continue;
+        }
+
+        // the light as if it all came from the direction sampled, the whole of an area light's
+        // radiance over the solid angle it covers
+        let irradiance = if sample.delta {
+            sample.radiance
+        } else {
+            sample.radiance / sample.pdf
+        };
+
+        let shadow = rec.spawn(sample.direction, march);
+        if world
+            .hit_parametric(&shadow, 0.0, sample.distance)
+            .is_some()
+        {
+            continue;
+        }
+        let visible = soft_shadow(world, &shadow, sample.distance, settings.softness, march);
+
+        light += (cos * visible / std::f64::consts::PI) * irradiance;
+    }
+
+    material.emitted(&rec) + albedo * light
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::csg::Union;
+    use crate::plane::Plane;
+    use crate::sphere::Sphere;
+    use crate::vec3::{origin, unit_x, unit_y};
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_shading_soft_shadow() {
+        let sphere = Sphere::new(5.0 * unit_x(), 1.0);
+        let march = MarchSettings::default();
+
+        // straight at it is fully shadowed, well clear of it fully lit
+        let r = Ray::new(origin(), unit_x());
+        assert_eq!(soft_shadow(&sphere, &r, 10.0, 8.0, &march), 0.0);
+        let r = Ray::new(origin(), unit_y());
+        assert_eq!(soft_shadow(&sphere, &r, 10.0, 8.0, &march), 1.0);
+
+        // and just past its edge, in the penumbra, softer shadows covering more
+        let r = Ray::new(origin(), Vec3::new(5.0, 1.1, 0.0));
+        let hard = soft_shadow(&sphere, &r, 10.0, 32.0, &march);
+        let soft = soft_shadow(&sphere, &r, 10.0, 4.0, &march);
+        assert!(0.0 < soft && soft < hard && hard < 1.0, "{} {}", soft, hard);
+
+        // unless the light is in front of it
+        assert_eq!(soft_shadow(&sphere, &r, 1.0, 8.0, &march), 1.0);
+    }
+
+    #[test]
+    fn test_shading_ambient_occlusion() {
+        let settings = ShadingSettings::default();
+        let floor = Plane::new(origin(), unit_y());
+        assert_relative_eq!(
+            ambient_occlusion(&floor, origin(), unit_y(), &settings),
+            1.0
+        );
+
+        // in the corner where a wall meets the floor it's darker
+        let corner = Union::from_children(vec![
+            Box::new(Plane::new(origin(), unit_y())),
+            Box::new(Plane::new(origin(), unit_x())),
+        ]);
+        let ao = ambient_occlusion(&corner, 0.01 * unit_x(), unit_y(), &settings);
+        assert!(ao < 0.5, "{}", ao);
+    }
+
+    #[test]
+    fn test_shading_preview() {
+        // a floor lit from above, with and without a ball over it
+        let march = MarchSettings::default();
+        let settings = ShadingSettings::default();
+        let lights = [Light::point(4.0 * unit_y(), Colour::new(16.0, 16.0, 16.0))];
+        let r = Ray::new(Vec3::new(-1.0, 1.0, 0.0), Vec3::new(1.0, -1.0, 0.0));
+
+        let mut world = HittableList::new();
+        world.add_implicit(Box::new(Plane::new(origin(), unit_y())));
+        let lit = preview(
+            &r,
+            &world,
+            &lights,
+            &march,
+            &settings,
+            &mut Diagnostics::default(),
+        );
+
+        world.add_implicit(Box::new(Sphere::new(2.0 * unit_y(), 0.5)));
+        let shadowed = preview(
+            &r,
+            &world,
+            &lights,
+            &march,
+            &settings,
+            &mut Diagnostics::default(),
+        );
+
+        // grey 0.5 times sky straight up, (0.5, 0.7, 1), and 1 / pi of the light's irradiance, 1
+        assert_relative_eq!(
+            lit.x(),
+            0.5 * (0.5 + 1.0 / std::f64::consts::PI),
+            epsilon = 1e-6
+        );
+        assert!(shadowed.x() < lit.x());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::dual::{self, Dual, Scalar};
 use crate::hittable::{HitRecord, Hittable, ImplicitSurface};
 use crate::ray::Ray;
 use crate::vec3::{self, Point3, Vec3};
@@ -15,14 +16,22 @@ impl Sphere {
             radius: r,
         }
     }
-}
 
-impl ImplicitSurface for Sphere {
     // signed distance function for a sphere is the distance from the centre minus its radius.
     // if v is outside of the sphere it will have a positive value, negative if inside, and
     // 0 if coincident.
+    fn distance<T: Scalar>(&self, v: [T; 3]) -> T {
+        dual::length(dual::offset(v, self.center)) - self.radius
+    }
+}
+
+impl ImplicitSurface for Sphere {
     fn signed_distance(&self, v: Vec3) -> f64 {
-        (v - self.center).length() - self.radius
+        self.distance(dual::constant(v))
+    }
+
+    fn signed_distance_dual(&self, v: [Dual; 3]) -> Dual {
+        self.distance(v)
     }
 
     fn gradient(&self, v: Vec3) -> Vec3 {
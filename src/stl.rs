@@ -0,0 +1,90 @@
+use std::io::{self, Write};
+
+use crate::mesh::Mesh;
+use crate::vec3::{normalise, Vec3};
+
+// STL stores a bag of independent triangles, each with its facet normal, so the shared vertices
+// and vertex normals of the mesh are not preserved.
+
+pub fn write_ascii(out: &mut impl Write, mesh: &Mesh, name: &str) -> io::Result<()> {
+    writeln!(out, "solid {}", name)?;
+    for t in &mesh.triangles {
+        writeln!(out, "  facet normal {}", normalise(mesh.facet_normal(*t)))?;
+        writeln!(out, "    outer loop")?;
+        for i in t {
+            writeln!(out, "      vertex {}", mesh.vertices[*i])?;
+        }
+        writeln!(out, "    endloop")?;
+        writeln!(out, "  endfacet")?;
+    }
+    writeln!(out, "endsolid {}", name)
+}
+
+// Binary STL is an 80 byte header, the triangle count, then 50 bytes per triangle: the normal and
+// three vertices as little-endian f32 triples followed by an unused attribute count.
+pub fn write_binary(out: &mut impl Write, mesh: &Mesh, name: &str) -> io::Result<()> {
+    let mut header = [0u8; 80];
+    let n = name.len().min(header.len());
+    header[..n].copy_from_slice(&name.as_bytes()[..n]);
+    out.write_all(&header)?;
+
+    out.write_all(&(mesh.triangles.len() as u32).to_le_bytes())?;
+
+    let write_vec = |out: &mut dyn Write, v: Vec3| -> io::Result<()> {
+        for x in [v.x(), v.y(), v.z()] {
+            out.write_all(&(x as f32).to_le_bytes())?;
+        }
+        Ok(())
+    };
+
+    for t in &mesh.triangles {
+        write_vec(out, normalise(mesh.facet_normal(*t)))?;
+        for i in t {
+            write_vec(out, mesh.vertices[*i])?;
+        }
+        out.write_all(&0u16.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vec3::{origin, unit_x, unit_y, unit_z};
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    fn triangle() -> Mesh {
+        let mut m = Mesh::new();
+        let a = m.add_vertex(origin(), unit_z());
+        let b = m.add_vertex(unit_x(), unit_z());
+        let c = m.add_vertex(unit_y(), unit_z());
+        m.add_triangle(a, b, c);
+        m
+    }
+
+    #[test]
+    fn test_stl_ascii() {
+        let mut out = Vec::new();
+        write_ascii(&mut out, &triangle(), "tri").unwrap();
+
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("solid tri\n"));
+        assert!(text.contains("facet normal 0 0 1\n"));
+        assert!(text.contains("vertex 1 0 0\n"));
+        assert!(text.ends_with("endsolid tri\n"));
+    }
+
+    #[test]
+    fn test_stl_binary() {
+        let mut out = Vec::new();
+        write_binary(&mut out, &triangle(), "tri").unwrap();
+
+        assert_eq!(out.len(), 80 + 4 + 50);
+        assert_eq!(&out[..3], b"tri");
+        assert_eq!(u32::from_le_bytes(out[80..84].try_into().unwrap()), 1);
+
+        // normal z component
+        assert_eq!(f32::from_le_bytes(out[92..96].try_into().unwrap()), 1.0);
+    }
+}
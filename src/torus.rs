@@ -0,0 +1,86 @@
+use crate::dual::{self, Dual, Scalar};
+use crate::hittable::ImplicitSurface;
+use crate::vec3::{normalise, Point3, Vec3};
+
+#[derive(Copy, Clone, Default)]
+pub struct Torus {
+    center: Point3,
+    axis: Vec3,
+    major_radius: f64,
+    minor_radius: f64,
+}
+
+impl Torus {
+    pub fn new(center: Point3, axis: Vec3, major: f64, minor: f64) -> Torus {
+        Torus {
+            center,
+            axis: normalise(axis),
+            major_radius: major,
+            minor_radius: minor,
+        }
+    }
+
+    // signed distance function for a torus is the distance to the circle running through the
+    // middle of the tube minus the tube radius. In the plane through the axis and v, that circle
+    // is the point major_radius out from the axis.
+    fn distance<T: Scalar>(&self, v: [T; 3]) -> T {
+        let q = dual::offset(v, self.center);
+        let height = dual::dot(q, self.axis);
+        let radial = dual::length(dual::sub(q, dual::along(height, self.axis)));
+
+        let out = radial - self.major_radius;
+        (out * out + height * height).sqrt() - self.minor_radius
+    }
+}
+
+impl ImplicitSurface for Torus {
+    fn signed_distance(&self, v: Vec3) -> f64 {
+        self.distance(dual::constant(v))
+    }
+
+    fn signed_distance_dual(&self, v: [Dual; 3]) -> Dual {
+        self.distance(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vec3::{origin, unit_x, unit_y, unit_z};
+    use approx::assert_relative_eq;
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_torus_sdf_coi() {
+        let t = Torus::new(origin(), unit_z(), 2.0, 0.5);
+        assert_relative_eq!(t.signed_distance(2.5 * unit_x()), 0.0);
+        assert_relative_eq!(t.signed_distance(1.5 * unit_y()), 0.0);
+        assert_relative_eq!(t.signed_distance(2.0 * unit_x() + 0.5 * unit_z()), 0.0);
+    }
+
+    #[test]
+    fn test_torus_sdf_outside_inside() {
+        let t = Torus::new(origin(), unit_z(), 2.0, 0.5);
+        assert_relative_eq!(t.signed_distance(2.0 * unit_x()), -0.5);
+        assert_relative_eq!(t.signed_distance(4.0 * unit_y()), 1.5);
+
+        // the hole in the middle is outside
+        assert_relative_eq!(t.signed_distance(origin()), 1.5);
+    }
+
+    #[test]
+    fn test_torus_gradient() {
+        let t = Torus::new(origin(), unit_z(), 2.0, 0.5);
+
+        let grad = t.gradient(3.0 * unit_x());
+        assert_relative_eq!(grad.x(), 1.0);
+        assert_relative_eq!(grad.y(), 0.0);
+        assert_relative_eq!(grad.z(), 0.0);
+
+        let grad = t.gradient(2.0 * unit_y() + unit_z());
+        assert_relative_eq!(grad.x(), 0.0);
+        assert_relative_eq!(grad.y(), 0.0);
+        assert_relative_eq!(grad.z(), 1.0);
+    }
+}
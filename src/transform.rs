@@ -0,0 +1,191 @@
+use crate::dual::{self, Dual};
+use crate::hittable::ImplicitSurface;
+use crate::interval::Interval;
+use crate::vec3::{Mat4, Vec3};
+
+// relative spread of singular values above which we consider a scale to be non-uniform
+const UNIFORM_TOL: f64 = 1e-9;
+
+// Places a surface in the world with an affine transform, so primitives can be modelled about the
+// origin and then moved, rotated, scaled or sheared into position.
+//
+// The child is evaluated in its own local space by pulling v back through the inverse. Local
+// distances are then scaled back up to world distances by the smallest singular value of the
+// transform. For a uniform scale that is exact, otherwise it is only a lower bound: the field is
+// still safe to sphere trace since it never overestimates, but it will take more steps.
+pub struct Transformed<S: ImplicitSurface> {
+    child: S,
+    to_local: Mat4,
+    scale: f64,
+    uniform: bool,
+}
+
+impl<S: ImplicitSurface> Transformed<S> {
+    pub fn new(child: S, to_world: Mat4) -> Transformed<S> {
+        let to_local = to_world.inverse();
+        let s = to_world.singular_values();
+
+        Transformed {
+            child,
+            to_local,
+            scale: s[2],
+            uniform: s[0] - s[2] <= UNIFORM_TOL * s[0],
+        }
+    }
+
+    // whether the transform scales all directions alike, so distances are exact rather than a
+    // lower bound, which callers may want to warn about
+    pub fn is_uniform(&self) -> bool {
+        self.uniform
+    }
+}
+
+impl<S: ImplicitSurface> ImplicitSurface for Transformed<S> {
+    fn signed_distance(&self, v: Vec3) -> f64 {
+        self.scale * self.child.signed_distance(self.to_local.transform_point(v))
+    }
+
+    // the local point carries the derivatives of the inverse, so the child's gradient comes out
+    // in world space, carried by the inverse transpose as normals should be
+    fn signed_distance_dual(&self, v: [Dual; 3]) -> Dual {
+        let local = dual::transform_point(&self.to_local, v);
+        self.child.signed_distance_dual(local) * self.scale
+    }
+
+    // The inverse stretches lengths by at most 1 / scale, which the scale back up cancels, so the
+    // child's bound holds as is over the ball pulled back into local space
+    fn lipschitz(&self, v: Vec3, radius: f64) -> f64 {
+        self.child
+            .lipschitz(self.to_local.transform_point(v), radius / self.scale)
+    }
+
+    fn signed_distance_interval(&self, v: [Interval; 3]) -> Interval {
+        let local = dual::transform_point(&self.to_local, v);
+        self.child.signed_distance_interval(local) * self.scale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use approx::assert_relative_eq;
+
+    use crate::csg::Difference;
+    use crate::cuboid::Cuboid;
+    use crate::ellipsoid::Ellipsoid;
+    use crate::ray::{MarchSettings, Ray};
+    use crate::sphere::Sphere;
+    use crate::vec3::{eq, normalise, origin, unit_x, unit_y, unit_z};
+
+    // Note this useful idiom: importing names from outer (for mod tests) scope.
+    use super::*;
+
+    #[test]
+    fn test_transformed_translate() {
+        let s = Transformed::new(
+            Sphere::new(origin(), 1.0),
+            Mat4::translation(5.0 * unit_x()),
+        );
+        assert_relative_eq!(s.signed_distance(4.0 * unit_x()), 0.0);
+        assert_relative_eq!(s.signed_distance(5.0 * unit_x()), -1.0);
+        assert!(eq(normalise(s.gradient(7.0 * unit_x())), unit_x()));
+    }
+
+    #[test]
+    fn test_transformed_uniform_scale() {
+        let s = Transformed::new(
+            Sphere::new(origin(), 1.0),
+            Mat4::scaling(Vec3::new(2.0, 2.0, 2.0)),
+        );
+
+        // distances are in world units, not the sphere's
+        assert_relative_eq!(s.signed_distance(2.0 * unit_y()), 0.0);
+        assert_relative_eq!(s.signed_distance(5.0 * unit_y()), 3.0);
+        assert_relative_eq!(s.signed_distance(origin()), -2.0);
+        assert!(s.is_uniform());
+    }
+
+    #[test]
+    fn test_transformed_rotate() {
+        // a long thin box along x turned to lie along y
+        let s = Transformed::new(
+            Cuboid::new(origin(), Vec3::new(3.0, 1.0, 1.0)),
+            Mat4::rotation(unit_z(), 0.5 * PI),
+        );
+        assert_relative_eq!(s.signed_distance(3.0 * unit_y()), 0.0, epsilon = 1e-12);
+        assert_relative_eq!(s.signed_distance(2.0 * unit_x()), 1.0, epsilon = 1e-12);
+        assert!(eq(s.gradient(4.0 * unit_y()), unit_y()));
+        assert!(eq(s.gradient(-2.0 * unit_x()), -unit_x()));
+    }
+
+    #[test]
+    fn test_transformed_non_uniform_is_bound() {
+        // an ellipsoid with semi-axes 3, 1, 1 made by stretching a sphere
+        let s = Transformed::new(
+            Sphere::new(origin(), 1.0),
+            Mat4::scaling(Vec3::new(3.0, 1.0, 1.0)),
+        );
+
+        assert!(!s.is_uniform());
+
+        // still zero on the surface
+        assert_relative_eq!(s.signed_distance(3.0 * unit_x()), 0.0);
+        assert_relative_eq!(s.signed_distance(unit_y()), 0.0);
+
+        // never more than the true distance of 2 along x
+        assert!(s.signed_distance(5.0 * unit_x()) <= 2.0);
+
+        // and the normal is perpendicular to the stretched surface
+        let p = Vec3::new(3.0 * 0.6, 0.8, 0.0);
+        let expect = normalise(Vec3::new(0.6 / 3.0, 0.8, 0.0));
+        assert!(eq(normalise(s.gradient(p)), expect));
+    }
+
+    #[test]
+    fn test_transformed_composite_gradient() {
+        // a CSG tree mixing surfaces with dual distances and one with a hand-written gradient,
+        // rotated and scaled, still has the exact gradient of its distance
+        let tree = Difference::new(
+            Box::new(Cuboid::new(origin(), Vec3::new(1.0, 1.0, 1.0))),
+            Box::new(Ellipsoid::new(unit_z(), Vec3::new(0.8, 0.5, 0.6))),
+        );
+        let s = Transformed::new(
+            tree,
+            Mat4::rotation(Vec3::new(1.0, 2.0, 3.0), 0.7) * Mat4::scaling(Vec3::new(2.0, 2.0, 2.0)),
+        );
+
+        let h = 1e-6;
+        for v in [Vec3::new(0.3, 2.5, -0.4), Vec3::new(0.2, 0.4, 1.1)] {
+            let fd = Vec3::new(
+                s.signed_distance(v + h * unit_x()) - s.signed_distance(v - h * unit_x()),
+                s.signed_distance(v + h * unit_y()) - s.signed_distance(v - h * unit_y()),
+                s.signed_distance(v + h * unit_z()) - s.signed_distance(v - h * unit_z()),
+            ) / (2.0 * h);
+            assert!(eq(s.gradient(v), fd));
+        }
+    }
+
+    #[test]
+    fn test_transformed_boxed() {
+        let child: Box<dyn ImplicitSurface> = Box::new(Sphere::new(origin(), 1.0));
+        let s = Transformed::new(child, Mat4::translation(unit_z()));
+        assert_relative_eq!(s.signed_distance(2.0 * unit_z()), 0.0);
+    }
+
+    #[test]
+    fn test_transformed_trace() {
+        let s = Transformed::new(
+            Sphere::new(origin(), 1.0),
+            Mat4::translation(-5.0 * unit_z()) * Mat4::scaling(Vec3::new(0.5, 0.5, 0.5)),
+        );
+
+        let r = Ray::new(origin(), -unit_z());
+        let rec = r
+            .trace(&s, 0.0, 10.0, &MarchSettings::default())
+            .record
+            .unwrap();
+        assert_relative_eq!(rec.t, 4.5, epsilon = 1e-6);
+        assert!(eq(rec.normal, unit_z()));
+    }
+}
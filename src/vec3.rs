@@ -1,4 +1,5 @@
 use crate::common;
+use std::f64::consts::PI;
 use std::fmt::{Display, Formatter, Result};
 use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
 
@@ -34,6 +35,10 @@ impl Vec3 {
         (self.x() * self.x()) + (self.y() * self.y()) + (self.z() * self.z())
     }
 
+    pub fn max_component(&self) -> f64 {
+        f64::max(self.x(), f64::max(self.y(), self.z()))
+    }
+
     pub fn random() -> Vec3 {
         Vec3::new(
             common::random_double(),
@@ -93,6 +98,15 @@ impl SubAssign for Vec3 {
     }
 }
 
+// Vec3 * Vec3, component-wise
+impl Mul for Vec3 {
+    type Output = Vec3;
+
+    fn mul(self, v: Vec3) -> Vec3 {
+        Vec3::new(self.x() * v.x(), self.y() * v.y(), self.z() * v.z())
+    }
+}
+
 // f64 * Vec3
 impl Mul<Vec3> for f64 {
     type Output = Vec3;
@@ -135,6 +149,52 @@ pub fn normalise(u: Vec3) -> Vec3 {
     u / u.length()
 }
 
+// v reflected in the plane with unit normal n
+pub fn reflect(v: Vec3, n: Vec3) -> Vec3 {
+    v - 2.0 * dot(v, n) * n
+}
+
+// The unit vector uv refracted through a surface with unit normal n facing it, where eta is the
+// ratio of the refractive indices on its side and the far side (Snell's law). There must be a
+// refracted ray, i.e. not total internal reflection.
+pub fn refract(uv: Vec3, n: Vec3, eta: f64) -> Vec3 {
+    let cos_theta = f64::min(dot(-uv, n), 1.0);
+    let perpendicular = eta * (uv + cos_theta * n);
+    let parallel = -f64::sqrt(f64::abs(1.0 - perpendicular.length_squared())) * n;
+    perpendicular + parallel
+}
+
+// some unit vector perpendicular to u, for when any choice of direction will do
+pub fn perpendicular(u: Vec3) -> Vec3 {
+    let a = if u.x().abs() < 0.9 {
+        unit_x()
+    } else {
+        unit_y()
+    };
+    normalise(cross(u, a))
+}
+
+// component-wise absolute value
+pub fn abs(u: Vec3) -> Vec3 {
+    Vec3::new(u.x().abs(), u.y().abs(), u.z().abs())
+}
+
+// component-wise maximum
+pub fn max(u: Vec3, v: Vec3) -> Vec3 {
+    Vec3::new(u.x().max(v.x()), u.y().max(v.y()), u.z().max(v.z()))
+}
+
+// component-wise minimum
+pub fn min(u: Vec3, v: Vec3) -> Vec3 {
+    Vec3::new(u.x().min(v.x()), u.y().min(v.y()), u.z().min(v.z()))
+}
+
+// component-wise sign, with zero treated as positive so it can be used to mirror back out of an octant
+pub fn sign(u: Vec3) -> Vec3 {
+    let s = |x: f64| if x < 0.0 { -1.0 } else { 1.0 };
+    Vec3::new(s(u.x()), s(u.y()), s(u.z()))
+}
+
 pub fn eq(u: Vec3, v: Vec3) -> bool {
     let tol = 0.001;
     (u - v).length() < tol
@@ -167,10 +227,187 @@ pub fn random_in_unit_sphere() -> Vec3 {
     }
 }
 
+// a random point in the unit disk in the xy plane
+pub fn random_in_unit_disk() -> Vec3 {
+    loop {
+        let p = Vec3::new(
+            common::random_double_range(-1.0, 1.0),
+            common::random_double_range(-1.0, 1.0),
+            0.0,
+        );
+        if p.length_squared() < 1.0 {
+            return p;
+        }
+    }
+}
+
 pub fn random_unit_vector() -> Vec3 {
     normalise(random_in_unit_sphere())
 }
 
+// A 4x4 matrix acting on homogeneous coordinates, stored by row. We only build affine transforms
+// (bottom row 0 0 0 1) so that is assumed wherever it makes things simpler, e.g. inverse.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Mat4 {
+    m: [[f64; 4]; 4],
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Mat4::identity()
+    }
+}
+
+impl Mat4 {
+    pub fn new(m: [[f64; 4]; 4]) -> Mat4 {
+        Mat4 { m }
+    }
+
+    pub fn identity() -> Mat4 {
+        Mat4::scaling(Vec3::new(1.0, 1.0, 1.0))
+    }
+
+    pub fn translation(t: Vec3) -> Mat4 {
+        let mut m = Mat4::identity();
+        m.m[0][3] = t.x();
+        m.m[1][3] = t.y();
+        m.m[2][3] = t.z();
+        m
+    }
+
+    pub fn scaling(s: Vec3) -> Mat4 {
+        Mat4::new([
+            [s.x(), 0.0, 0.0, 0.0],
+            [0.0, s.y(), 0.0, 0.0],
+            [0.0, 0.0, s.z(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // right-handed rotation by angle radians around axis, via Rodrigues' formula
+    pub fn rotation(axis: Vec3, angle: f64) -> Mat4 {
+        let a = normalise(axis);
+        let (x, y, z) = (a.x(), a.y(), a.z());
+        let c = angle.cos();
+        let s = angle.sin();
+        let t = 1.0 - c;
+
+        Mat4::new([
+            [t * x * x + c, t * x * y - s * z, t * x * z + s * y, 0.0],
+            [t * x * y + s * z, t * y * y + c, t * y * z - s * x, 0.0],
+            [t * x * z - s * y, t * y * z + s * x, t * z * z + c, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> f64 {
+        self.m[row][col]
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut t = Mat4::identity();
+        for (i, row) in self.m.iter().enumerate() {
+            for (j, x) in row.iter().enumerate() {
+                t.m[j][i] = *x;
+            }
+        }
+        t
+    }
+
+    // inverse of an affine transform: invert the linear part and undo the translation with it
+    pub fn inverse(&self) -> Mat4 {
+        let r0 = Vec3::new(self.m[0][0], self.m[0][1], self.m[0][2]);
+        let r1 = Vec3::new(self.m[1][0], self.m[1][1], self.m[1][2]);
+        let r2 = Vec3::new(self.m[2][0], self.m[2][1], self.m[2][2]);
+
+        // the columns of the inverse are the cross products of pairs of rows over the determinant
+        let c0 = cross(r1, r2);
+        let c1 = cross(r2, r0);
+        let c2 = cross(r0, r1);
+        let det = dot(r0, c0);
+        let (c0, c1, c2) = (c0 / det, c1 / det, c2 / det);
+
+        let mut inv = Mat4::new([
+            [c0.x(), c1.x(), c2.x(), 0.0],
+            [c0.y(), c1.y(), c2.y(), 0.0],
+            [c0.z(), c1.z(), c2.z(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+
+        let t = -inv.transform_vector(Vec3::new(self.m[0][3], self.m[1][3], self.m[2][3]));
+        inv.m[0][3] = t.x();
+        inv.m[1][3] = t.y();
+        inv.m[2][3] = t.z();
+        inv
+    }
+
+    pub fn transform_point(&self, p: Point3) -> Point3 {
+        self.transform_vector(p) + Vec3::new(self.m[0][3], self.m[1][3], self.m[2][3])
+    }
+
+    // vectors are directions so are unaffected by translation
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let row = |r: [f64; 4]| r[0] * v.x() + r[1] * v.y() + r[2] * v.z();
+        Vec3::new(row(self.m[0]), row(self.m[1]), row(self.m[2]))
+    }
+
+    // Singular values of the linear part, largest first. They are how much the transform stretches
+    // space along its principal directions, and are the square roots of the eigenvalues of
+    // A^T A, found with the closed form for symmetric 3x3 matrices.
+    pub fn singular_values(&self) -> [f64; 3] {
+        let mut ata = [[0.0; 3]; 3];
+        for (i, row) in ata.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = (0..3).map(|k| self.m[k][i] * self.m[k][j]).sum();
+            }
+        }
+
+        let p1 = ata[0][1].powi(2) + ata[0][2].powi(2) + ata[1][2].powi(2);
+        let mut eig = if p1 == 0.0 {
+            [ata[0][0], ata[1][1], ata[2][2]]
+        } else {
+            let q = (ata[0][0] + ata[1][1] + ata[2][2]) / 3.0;
+            let p2 = (ata[0][0] - q).powi(2)
+                + (ata[1][1] - q).powi(2)
+                + (ata[2][2] - q).powi(2)
+                + 2.0 * p1;
+            let p = f64::sqrt(p2 / 6.0);
+
+            let mut b = ata;
+            for (i, row) in b.iter_mut().enumerate() {
+                for (j, x) in row.iter_mut().enumerate() {
+                    *x = (*x - if i == j { q } else { 0.0 }) / p;
+                }
+            }
+            let det_b = b[0][0] * (b[1][1] * b[2][2] - b[1][2] * b[2][1])
+                - b[0][1] * (b[1][0] * b[2][2] - b[1][2] * b[2][0])
+                + b[0][2] * (b[1][0] * b[2][1] - b[1][1] * b[2][0]);
+
+            let phi = f64::acos(common::clamp(det_b / 2.0, -1.0, 1.0)) / 3.0;
+            let e0 = q + 2.0 * p * phi.cos();
+            let e2 = q + 2.0 * p * (phi + 2.0 * PI / 3.0).cos();
+            [e0, 3.0 * q - e0 - e2, e2]
+        };
+
+        eig.sort_by(|a, b| b.total_cmp(a));
+        eig.map(|e| f64::sqrt(f64::max(e, 0.0)))
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, n: Mat4) -> Mat4 {
+        let mut out = Mat4::identity();
+        for (i, row) in out.m.iter_mut().enumerate() {
+            for (j, x) in row.iter_mut().enumerate() {
+                *x = (0..4).map(|k| self.m[i][k] * n.m[k][j]).sum();
+            }
+        }
+        out
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use approx::assert_relative_eq;
@@ -197,15 +434,15 @@ mod tests {
     fn test_vec_rt2_length() {
         let v = unit_x() + unit_y();
         assert_relative_eq!(v.length_squared(), 2.0);
-        assert_relative_eq!(v.length(), 1.4142135623730951);
+        assert_relative_eq!(v.length(), std::f64::consts::SQRT_2);
 
         let v = unit_y() + unit_z();
         assert_relative_eq!(v.length_squared(), 2.0);
-        assert_relative_eq!(v.length(), 1.4142135623730951);
+        assert_relative_eq!(v.length(), std::f64::consts::SQRT_2);
 
         let v = unit_x() + unit_z();
         assert_relative_eq!(v.length_squared(), 2.0);
-        assert_relative_eq!(v.length(), 1.4142135623730951);
+        assert_relative_eq!(v.length(), std::f64::consts::SQRT_2);
     }
 
     #[test]
@@ -250,12 +487,89 @@ mod tests {
         assert_relative_eq!(0.0, v.z());
     }
 
+    #[test]
+    fn test_vec_component_wise() {
+        let u = Vec3::new(-1.0, 2.0, -3.0);
+        let v = Vec3::new(0.5, -4.0, 1.0);
+
+        assert!(eq(abs(u), Vec3::new(1.0, 2.0, 3.0)));
+        assert!(eq(max(u, v), Vec3::new(0.5, 2.0, 1.0)));
+        assert!(eq(min(u, v), Vec3::new(-1.0, -4.0, -3.0)));
+        assert!(eq(sign(u), Vec3::new(-1.0, 1.0, -1.0)));
+        assert!(eq(u * v, Vec3::new(-0.5, -8.0, -3.0)));
+        assert_relative_eq!(u.max_component(), 2.0);
+    }
+
+    #[test]
+    fn test_vec_perpendicular() {
+        for u in [unit_x(), unit_y(), unit_z(), Vec3::new(1.0, 2.0, 3.0)] {
+            let p = perpendicular(u);
+            assert_relative_eq!(dot(u, p), 0.0);
+            assert_relative_eq!(p.length(), 1.0);
+        }
+    }
+
+    #[test]
+    fn test_vec_reflect_refract() {
+        let v = normalise(Vec3::new(1.0, -1.0, 0.0));
+        assert!(eq(reflect(v, unit_y()), Vec3::new(v.x(), -v.y(), 0.0)));
+
+        // straight through with no change of index, and bent towards the normal going into glass
+        assert!(eq(refract(v, unit_y(), 1.0), v));
+        let r = refract(v, unit_y(), 1.0 / 1.5);
+        assert_relative_eq!(r.length(), 1.0);
+        assert_relative_eq!(r.x(), v.x() / 1.5);
+    }
+
     #[test]
     fn test_vec_equal() {
-        assert_eq!(eq(unit_x(), unit_x()), true);
-        assert_eq!(eq(unit_x(), unit_z()), false);
+        assert!(eq(unit_x(), unit_x()));
+        assert!(!eq(unit_x(), unit_z()));
 
         // to tolerance
-        assert_eq!(eq(unit_z(), 1.000001 * unit_z()), true);
+        assert!(eq(unit_z(), 1.000001 * unit_z()));
+    }
+
+    #[test]
+    fn test_mat_transform() {
+        let m = Mat4::translation(unit_x()) * Mat4::scaling(Vec3::new(2.0, 2.0, 2.0));
+        assert!(eq(m.transform_point(unit_y()), Vec3::new(1.0, 2.0, 0.0)));
+        assert!(eq(m.transform_vector(unit_y()), 2.0 * unit_y()));
+
+        let r = Mat4::rotation(unit_z(), 0.5 * PI);
+        assert!(eq(r.transform_point(unit_x()), unit_y()));
+        assert!(eq(r.transform_point(unit_y()), -unit_x()));
+        assert!(eq(r.transform_point(unit_z()), unit_z()));
+    }
+
+    #[test]
+    fn test_mat_inverse() {
+        let m = Mat4::translation(Vec3::new(1.0, -2.0, 3.0))
+            * Mat4::rotation(Vec3::new(1.0, 1.0, 0.0), 0.3)
+            * Mat4::scaling(Vec3::new(2.0, 0.5, 3.0));
+        let inv = m.inverse();
+
+        let v = Vec3::new(4.0, 5.0, 6.0);
+        assert!(eq(inv.transform_point(m.transform_point(v)), v));
+        assert!(eq((inv * m).transform_point(v), v));
+        assert!(eq(
+            m.transpose().transpose().transform_point(v),
+            m.transform_point(v)
+        ));
+    }
+
+    #[test]
+    fn test_mat_singular_values() {
+        let m = Mat4::rotation(Vec3::new(1.0, 2.0, 3.0), 1.0)
+            * Mat4::scaling(Vec3::new(2.0, 0.5, 3.0))
+            * Mat4::translation(unit_x());
+        let s = m.singular_values();
+        assert_relative_eq!(s[0], 3.0, epsilon = 1e-9);
+        assert_relative_eq!(s[1], 2.0, epsilon = 1e-9);
+        assert_relative_eq!(s[2], 0.5, epsilon = 1e-9);
+
+        let s = Mat4::rotation(unit_y(), 0.7).singular_values();
+        assert_relative_eq!(s[0], 1.0, epsilon = 1e-9);
+        assert_relative_eq!(s[2], 1.0, epsilon = 1e-9);
     }
 }